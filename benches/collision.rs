@@ -0,0 +1,79 @@
+//! How long one tick of collision detection takes as the arena fills up.
+//!
+//! Each case runs `move_swept`, `check_for_intersections` and `track_collisions` once over a grid
+//! of static bricks with some balls flying through it. Run with `cargo bench --bench collision`.
+use std::time::Duration;
+
+use bevy::prelude::*;
+use breakout2::{
+    collision::{check_for_intersections, move_swept, track_collisions, ColliderShape, CollisionTracker},
+    Collider, CollisionEvent, Velocity,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+const BRICK_SIZE: Vec2 = Vec2::new(100., 30.);
+const BRICK_GAP: f32 = 5.0;
+const BALL_DIAMETER: f32 = 30.0;
+const BALL_SPEED: f32 = 400.0;
+const TICK: Duration = Duration::from_micros(15_625);
+
+/// A square-ish field of `bricks` bricks with `balls` balls spread over it, ready to run one tick.
+fn arena(bricks: usize, balls: usize) -> (World, Schedule) {
+    let mut world = World::new();
+    let mut time = Time::<()>::default();
+    time.advance_by(TICK);
+    world.insert_resource(time);
+    world.init_resource::<Events<CollisionEvent>>();
+    world.init_resource::<CollisionTracker>();
+
+    let columns = (bricks as f32).sqrt().ceil() as usize;
+    let step = BRICK_SIZE + BRICK_GAP;
+    for index in 0..bricks {
+        let position = Vec2::new((index % columns) as f32, (index / columns) as f32) * step;
+        world.spawn((
+            Transform::from_translation(position.extend(0.0)).with_scale(BRICK_SIZE.extend(1.0)),
+            Collider,
+        ));
+    }
+    let field = Vec2::splat(columns as f32) * step;
+    for index in 0..balls {
+        // Spread along the field's diagonal, heading off at different angles
+        let along = (index as f32 + 0.5) / balls as f32;
+        let angle = along * std::f32::consts::TAU;
+        world.spawn((
+            Transform::from_translation((field * along).extend(1.0)).with_scale(Vec3::new(BALL_DIAMETER, BALL_DIAMETER, 1.0)),
+            ColliderShape::Circle,
+            Collider,
+            Velocity(Vec2::from_angle(angle) * BALL_SPEED),
+        ));
+    }
+
+    let mut schedule = Schedule::default();
+    schedule.add_systems((move_swept, check_for_intersections, track_collisions).chain());
+    // Initializes the systems, so only the tick itself is measured
+    schedule.initialize(&mut world).unwrap();
+    (world, schedule)
+}
+
+fn collision_tick(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collision_tick");
+    for bricks in [100, 1_000, 5_000] {
+        for balls in [1, 10, 50] {
+            group.bench_with_input(
+                BenchmarkId::new(format!("{balls}_balls"), bricks),
+                &(bricks, balls),
+                |b, &(bricks, balls)| {
+                    b.iter_batched_ref(
+                        || arena(bricks, balls),
+                        |(world, schedule)| schedule.run(world),
+                        BatchSize::LargeInput,
+                    );
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, collision_tick);
+criterion_main!(benches);
@@ -0,0 +1,9 @@
+//! The plain game, embedded through `BreakoutPlugin`.
+use bevy::prelude::*;
+use breakout2::BreakoutPlugin;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, BreakoutPlugin::default()))
+        .run();
+}
@@ -0,0 +1,265 @@
+//! The brick type catalogue.
+//!
+//! A brick type is a named entry in `BrickConfig::types`. It can swap out the plain brick's
+//! components, color, points and hit points, and add any of the behaviours below:
+//! - indestructible bricks are just bricks without `Destructable`, so nothing here handles them,
+//! - `Explosive` bricks destroy every `Destructable` around them when they are destroyed,
+//! - `Regenerating` bricks are hidden instead of despawned and grow back after a delay,
+//! - `Patrolling` bricks move back and forth horizontally, like a paddle without a player.
+//!
+//! Bricks with more than one hit point, like the `tough` type, fade as they take damage unless
+//! `BrickConfig::hit_point_colors` picks their colors.
+//!
+//! A level is complete once every `Destructable` is gone, so indestructible bricks and
+//! regenerating bricks that are waiting to grow back don't hold it up.
+use bevy::{ecs::system::EntityCommands, prelude::*, utils::HashSet};
+
+use crate::{
+    config::{BrickTypeConfig, GameConfig},
+    stash::StashCommandsExt,
+    Collider, DestroyedEvent, Destructable,
+};
+
+/// Which of the `BrickConfig::types` a brick was spawned as. Plain bricks don't have one.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct BrickType(pub String);
+
+/// Destroying this also destroys every `Destructable` whose center is within `radius` of its own.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Explosive {
+    pub radius: f32,
+}
+
+/// Grows back with `hit_points` `delay` seconds after being destroyed.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Regenerating {
+    pub delay: f32,
+    pub hit_points: u32,
+}
+
+/// Counts down until a destroyed `Regenerating` brick grows back.
+#[derive(Component, Debug)]
+pub struct Regrowing(pub Timer);
+
+/// Moves horizontally at `speed`, turning around at `min_x` and `max_x`. Negative speeds move left.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Patrolling {
+    pub speed: f32,
+    pub min_x: f32,
+    pub max_x: f32,
+}
+
+/// Adds the behaviours `brick_type` asks for to a brick spawned at `position`.
+/// Patrols are cut short where they would run into a side wall.
+pub(crate) fn insert_behaviours(
+    entity: &mut EntityCommands,
+    config: &GameConfig,
+    brick_type: &BrickTypeConfig,
+    position: Vec2,
+    hit_points: u32,
+) {
+    if let Some(radius) = brick_type.explosion_radius {
+        entity.insert(Explosive { radius });
+    }
+    if let Some(delay) = brick_type.regrow_after {
+        entity.insert(Regenerating { delay, hit_points });
+    }
+    if let Some(patrol) = &brick_type.patrol {
+        let arena = &config.arena;
+        let reach = arena.wall_thickness / 2.0 + config.brick.size[0] / 2.0;
+        let (left, right) = (arena.left_wall + reach, arena.right_wall - reach);
+        entity.insert(Patrolling {
+            speed: patrol.speed,
+            min_x: (position.x - patrol.distance).max(left).min(position.x),
+            max_x: (position.x + patrol.distance).min(right).max(position.x),
+        });
+    }
+}
+
+pub(crate) fn patrol(mut query: Query<(&mut Transform, &mut Patrolling)>, time: Res<Time>) {
+    for (mut transform, mut patrolling) in &mut query {
+        let x = transform.translation.x + patrolling.speed * time.delta_secs();
+        if x < patrolling.min_x || x > patrolling.max_x {
+            patrolling.speed = -patrolling.speed;
+        }
+        transform.translation.x = x.clamp(patrolling.min_x, patrolling.max_x);
+    }
+}
+
+// The destructor of the first brick in a chain of explosions gets credit for all of them
+pub(crate) fn explode(
+    mut commands: Commands,
+    mut destroyed_events: EventReader<DestroyedEvent>,
+    explosive_query: Query<(&Transform, &Explosive)>,
+    destructable_query: Query<(Entity, &Transform), With<Destructable>>,
+) {
+    let events: Vec<DestroyedEvent> = destroyed_events.read().copied().collect();
+    let mut destroyed: HashSet<Entity> = events.iter().map(|event| event.destroyed).collect();
+    let mut blasts: Vec<DestroyedEvent> = events
+        .into_iter()
+        .filter(|event| explosive_query.contains(event.destroyed))
+        .collect();
+    while let Some(blast) = blasts.pop() {
+        let Ok((transform, explosive)) = explosive_query.get(blast.destroyed) else {
+            continue;
+        };
+        let center = transform.translation.truncate();
+        for (entity, other_transform) in &destructable_query {
+            let in_range = other_transform.translation.truncate().distance(center) <= explosive.radius;
+            if in_range && destroyed.insert(entity) {
+                let event = DestroyedEvent { destructor: blast.destructor, destroyed: entity };
+                commands.send_event(event);
+                blasts.push(event);
+            }
+        }
+    }
+}
+
+// Regenerating bricks are hidden and lose their collider instead of being despawned
+pub(crate) fn start_regrowing(
+    mut commands: Commands,
+    mut destroyed_events: EventReader<DestroyedEvent>,
+    regenerating_query: Query<&Regenerating>,
+) {
+    for event in destroyed_events.read() {
+        let Ok(regenerating) = regenerating_query.get(event.destroyed) else {
+            continue;
+        };
+        commands
+            .entity(event.destroyed)
+            .remove::<Destructable>()
+            .stash::<Collider>()
+            .insert((
+                Visibility::Hidden,
+                Regrowing(Timer::from_seconds(regenerating.delay, TimerMode::Once)),
+            ));
+    }
+}
+
+pub(crate) fn regrow(
+    mut commands: Commands,
+    mut query: Query<(Entity, &Regenerating, &mut Regrowing)>,
+    time: Res<Time>,
+) {
+    for (entity, regenerating, mut regrowing) in &mut query {
+        if regrowing.0.tick(time.delta()).finished() {
+            commands
+                .entity(entity)
+                .remove::<Regrowing>()
+                .restore::<Collider>()
+                .insert((
+                    Destructable {
+                        hit_points: regenerating.hit_points,
+                    },
+                    Visibility::Inherited,
+                ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::config::PatrolConfig;
+
+    fn world(tick: Duration) -> World {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(tick);
+        world.insert_resource(time);
+        world.init_resource::<Events<DestroyedEvent>>();
+        world
+    }
+
+    fn brick(world: &mut World, x: f32) -> Entity {
+        world.spawn((Transform::from_xyz(x, 0.0, 0.0), Destructable { hit_points: 1 })).id()
+    }
+
+    #[test]
+    fn explosions_chain_and_credit_the_first_destructor() {
+        let mut world = world(Duration::ZERO);
+        let ball = world.spawn_empty().id();
+        let first = brick(&mut world, 0.0);
+        let second = brick(&mut world, 40.0);
+        let third = brick(&mut world, 80.0);
+        let out_of_range = brick(&mut world, 200.0);
+        world.entity_mut(first).insert(Explosive { radius: 50.0 });
+        world.entity_mut(second).insert(Explosive { radius: 50.0 });
+
+        world.send_event(DestroyedEvent { destructor: ball, destroyed: first });
+        world.run_system_once(explode).unwrap();
+
+        let events = world.resource::<Events<DestroyedEvent>>();
+        let mut destroyed: Vec<Entity> = events
+            .iter_current_update_events()
+            .filter(|event| event.destroyed != first)
+            .inspect(|event| assert_eq!(event.destructor, ball))
+            .map(|event| event.destroyed)
+            .collect();
+        destroyed.sort();
+        assert_eq!(destroyed, vec![second, third]);
+        assert!(!destroyed.contains(&out_of_range));
+    }
+
+    #[test]
+    fn regenerating_brick_grows_back() {
+        let mut world = world(Duration::from_secs(3));
+        let ball = world.spawn_empty().id();
+        let brick = world.spawn((Destructable { hit_points: 0 }, Collider, Regenerating { delay: 2.0, hit_points: 3 })).id();
+
+        world.send_event(DestroyedEvent { destructor: ball, destroyed: brick });
+        world.run_system_once(start_regrowing).unwrap();
+        assert!(!world.entity(brick).contains::<Destructable>());
+        assert!(!world.entity(brick).contains::<Collider>());
+        assert_eq!(world.get::<Visibility>(brick), Some(&Visibility::Hidden));
+
+        world.run_system_once(regrow).unwrap();
+        assert_eq!(world.get::<Destructable>(brick).map(|destructable| destructable.hit_points), Some(3));
+        assert!(world.entity(brick).contains::<Collider>());
+        assert!(!world.entity(brick).contains::<Regrowing>());
+        assert_eq!(world.get::<Visibility>(brick), Some(&Visibility::Inherited));
+    }
+
+    #[test]
+    fn patrol_turns_around_at_its_ends() {
+        let mut world = world(Duration::from_millis(100));
+        let brick = world
+            .spawn((Transform::from_xyz(5.0, 0.0, 0.0), Patrolling { speed: 100.0, min_x: -10.0, max_x: 10.0 }))
+            .id();
+
+        world.run_system_once(patrol).unwrap();
+
+        assert_eq!(world.get::<Transform>(brick).unwrap().translation.x, 10.0);
+        assert_eq!(world.get::<Patrolling>(brick).unwrap().speed, -100.0);
+    }
+
+    #[test]
+    fn patrol_stops_short_of_the_side_walls() {
+        let config = GameConfig::default();
+        let reach = config.arena.wall_thickness / 2.0 + config.brick.size[0] / 2.0;
+        let right = config.arena.right_wall - reach;
+        let brick_type = BrickTypeConfig {
+            patrol: Some(PatrolConfig { speed: 100.0, distance: 1000.0 }),
+            ..default()
+        };
+
+        let mut world = world(Duration::ZERO);
+        let mut patrol_from = |x: f32| {
+            let brick = world.spawn_empty().id();
+            insert_behaviours(&mut world.commands().entity(brick), &config, &brick_type, Vec2::new(x, 0.0), 1);
+            world.flush();
+            *world.get::<Patrolling>(brick).unwrap()
+        };
+
+        let patrolling = patrol_from(0.0);
+        assert_eq!((patrolling.min_x, patrolling.max_x), (config.arena.left_wall + reach, right));
+        // A brick placed closer to the wall than it can reach stays put on that side
+        let patrolling = patrol_from(right + 5.0);
+        assert_eq!(patrolling.max_x, right + 5.0);
+        assert!(patrolling.min_x < patrolling.max_x);
+    }
+}
@@ -0,0 +1,137 @@
+//! Narrows down which colliders could be touching, so they don't all have to be tested in pairs.
+//!
+//! Static colliders are bucketed into a `SpatialGrid` of square cells. Dynamic ones are only
+//! tested against the static colliders in the cells they cover, and against each other. Two static
+//! colliders, like a brick and its neighbour, are never paired, since neither can have moved into
+//! the other.
+use bevy::{
+    math::bounding::{Aabb2d, IntersectsVolume},
+    prelude::*,
+    utils::HashMap,
+};
+
+/// How wide a grid cell is. Around the size of a brick, so most bricks only cover a few cells.
+pub const CELL_SIZE: f32 = 64.0;
+
+/// Indices of boxes, bucketed by the cells they cover.
+#[derive(Debug, Default)]
+pub struct SpatialGrid {
+    cells: HashMap<IVec2, Vec<usize>>,
+}
+
+impl SpatialGrid {
+    pub fn insert(&mut self, index: usize, aabb: Aabb2d) {
+        for cell in cells(aabb) {
+            self.cells.entry(cell).or_default().push(index);
+        }
+    }
+
+    /// Every index whose box shares a cell with `aabb`, each one once.
+    pub fn query(&self, aabb: Aabb2d) -> Vec<usize> {
+        let mut found: Vec<usize> = cells(aabb)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+}
+
+fn cells(aabb: Aabb2d) -> impl Iterator<Item = IVec2> {
+    let min = (aabb.min / CELL_SIZE).floor().as_ivec2();
+    let max = (aabb.max / CELL_SIZE).floor().as_ivec2();
+    (min.x..=max.x).flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+}
+
+/// Pairs of indices into `boxes` whose boxes overlap and where at least one is dynamic.
+/// Each pair is `(lower, higher)`, the order `iter_combinations` would give.
+pub fn overlapping_pairs(boxes: &[(Aabb2d, bool)]) -> Vec<(usize, usize)> {
+    let mut grid = SpatialGrid::default();
+    let mut dynamic = Vec::new();
+    for (index, (aabb, is_dynamic)) in boxes.iter().enumerate() {
+        if *is_dynamic {
+            dynamic.push(index);
+        } else {
+            grid.insert(index, *aabb);
+        }
+    }
+
+    let mut pairs = Vec::new();
+    for (position, &index) in dynamic.iter().enumerate() {
+        let aabb = boxes[index].0;
+        let statics = grid.query(aabb).into_iter();
+        // There are only a few dynamic colliders, so they're paired with each other directly
+        let dynamics = dynamic[position + 1..].iter().copied();
+        for other in statics.chain(dynamics) {
+            if aabb.intersects(&boxes[other].0) {
+                pairs.push((index.min(other), index.max(other)));
+            }
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_force(boxes: &[(Aabb2d, bool)]) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for (index1, (aabb1, dynamic1)) in boxes.iter().enumerate() {
+            for (index2, (aabb2, dynamic2)) in boxes.iter().enumerate().skip(index1 + 1) {
+                if (*dynamic1 || *dynamic2) && aabb1.intersects(aabb2) {
+                    pairs.push((index1, index2));
+                }
+            }
+        }
+        pairs
+    }
+
+    fn sorted(mut pairs: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        pairs.sort_unstable();
+        pairs
+    }
+
+    #[test]
+    fn matches_testing_every_pair() {
+        // A small linear congruential generator keeps the layout the same on every run
+        let mut seed = 0x2545_f491_u32;
+        let mut next = move |range: f32| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 8) as f32 / (1 << 24) as f32 * range
+        };
+        let boxes: Vec<(Aabb2d, bool)> = (0..200)
+            .map(|index| {
+                let center = Vec2::new(next(800.0) - 400.0, next(600.0) - 300.0);
+                // Up to a few cells across, so plenty of boxes share more than one cell
+                let half_size = Vec2::new(next(CELL_SIZE * 2.0) + 1.0, next(CELL_SIZE) + 1.0);
+                (Aabb2d::new(center, half_size), index % 5 == 0)
+            })
+            .collect();
+
+        let expected = brute_force(&boxes);
+        assert!(!expected.is_empty());
+        assert_eq!(sorted(overlapping_pairs(&boxes)), expected);
+    }
+
+    #[test]
+    fn boxes_sharing_several_cells_are_paired_once() {
+        let boxes = [
+            (Aabb2d::new(Vec2::ZERO, Vec2::splat(CELL_SIZE * 2.0)), false),
+            (Aabb2d::new(Vec2::splat(10.0), Vec2::splat(CELL_SIZE * 1.5)), true),
+        ];
+        assert_eq!(overlapping_pairs(&boxes), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn static_pairs_are_skipped() {
+        let boxes = [
+            (Aabb2d::new(Vec2::ZERO, Vec2::splat(20.0)), false),
+            (Aabb2d::new(Vec2::splat(10.0), Vec2::splat(20.0)), false),
+            (Aabb2d::new(Vec2::splat(-10.0), Vec2::splat(20.0)), true),
+        ];
+        assert_eq!(sorted(overlapping_pairs(&boxes)), vec![(0, 2), (1, 2)]);
+    }
+}
@@ -0,0 +1,646 @@
+//! Finds out which entities touch, and sends a `CollisionEvent` when a pair starts touching, for
+//! every tick it keeps touching, and when it stops. See `CollisionPhase`.
+//!
+//! Every entity is a box built from its `transform.scale`, unless its `ColliderShape` says
+//! otherwise. Entities with both `Velocity` and a `Collider` are moved by `move_swept`, which sweeps
+//! their shape along the tick's movement and stops it where it first touches another `Collider`.
+//! That way a fast ball or a long tick can't carry a ball through a thin wall. Whatever is left of
+//! the tick's movement after the hit is dropped, and the bounce is left to the systems reading the
+//! event. `check_for_intersections` then reports everything that overlaps once things have moved.
+//! Both only test entities with a `Collider`, in the pairs that `broadphase` finds could be touching
+//! and whose `CollisionLayers` let them collide. They report what they find to the
+//! `CollisionTracker`, and `track_collisions` compares it with the previous tick to send the events.
+//! Each event carries a `Manifold`, and `resolve_penetration` uses it to push overlapping bodies
+//! apart.
+use bevy::{
+    math::bounding::{Aabb2d, AabbCast2d, BoundingCircle, BoundingCircleCast, BoundingVolume, IntersectsVolume, RayCast2d},
+    prelude::*,
+};
+
+use bevy::utils::HashMap;
+use serde::Deserialize;
+
+use crate::{
+    broadphase::{self, SpatialGrid},
+    Collider, CollisionEvent, Controllable, EntityType, Mass, Velocity,
+};
+
+/// How far short of the time of impact a swept entity stops, so it ends the tick just apart from
+/// what it hit rather than overlapping it.
+const SKIN: f32 = 0.01;
+
+/// Where a `CollisionEvent` is in the life of a contact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionPhase {
+    /// The pair touches this tick but didn't last tick.
+    Started,
+    /// The pair touched last tick too.
+    Persisting,
+    /// The pair touched last tick but doesn't anymore. Either entity may have been despawned since.
+    Ended,
+}
+
+/// The pairs of entities that touch, this tick and last tick.
+/// Pairs are keyed by their lower entity first, with the manifold flipped to match.
+#[derive(Resource, Debug, Default)]
+pub struct CollisionTracker {
+    touching: HashMap<(Entity, Entity), Manifold>,
+    previous: HashMap<(Entity, Entity), Manifold>,
+}
+
+impl CollisionTracker {
+    /// Records that `e1` touches `e2` this tick. Only the first report of a pair counts.
+    pub fn report(&mut self, e1: Entity, e2: Entity, manifold: Manifold) {
+        let (key, manifold) = if e1 < e2 { ((e1, e2), manifold) } else { ((e2, e1), manifold.flipped()) };
+        self.touching.entry(key).or_insert(manifold);
+    }
+
+    /// Forgets every pair, without sending `Ended` for them.
+    pub fn clear(&mut self) {
+        self.touching.clear();
+        self.previous.clear();
+    }
+}
+
+/// Turns this tick's contacts into `CollisionEvent`s, then starts collecting the next tick's.
+pub fn track_collisions(mut tracker: ResMut<CollisionTracker>, mut collision_events: EventWriter<CollisionEvent>) {
+    let tracker = &mut *tracker;
+    for (&(e1, e2), &manifold) in &tracker.touching {
+        let phase = if tracker.previous.contains_key(&(e1, e2)) {
+            CollisionPhase::Persisting
+        } else {
+            CollisionPhase::Started
+        };
+        collision_events.send(CollisionEvent { e1, e2, hit_side_of_e1: manifold.side(), manifold, phase });
+    }
+    // Ended pairs keep the manifold they last touched with
+    for (&(e1, e2), &manifold) in &tracker.previous {
+        if !tracker.touching.contains_key(&(e1, e2)) {
+            let phase = CollisionPhase::Ended;
+            collision_events.send(CollisionEvent { e1, e2, hit_side_of_e1: manifold.side(), manifold, phase });
+        }
+    }
+    tracker.previous = std::mem::take(&mut tracker.touching);
+}
+
+/// Pushes overlapping entities apart along their manifold's normal. Only entities with `Velocity`
+/// are pushed, so a ball is pushed out of a paddle or a wall but a paddle is never pushed by
+/// anything. Two moving entities split the push, the lighter one moving further, see `Mass`.
+pub fn resolve_penetration(
+    mut collision_events: EventReader<CollisionEvent>,
+    mut body_query: Query<(&mut Transform, Has<Velocity>, Option<&Mass>), With<Collider>>,
+) {
+    for event in collision_events.read() {
+        if event.phase == CollisionPhase::Ended || event.manifold.depth <= 0. {
+            continue;
+        }
+        let Ok([(mut transform1, movable1, mass1), (mut transform2, movable2, mass2)]) =
+            body_query.get_many_mut([event.e1, event.e2])
+        else {
+            continue;
+        };
+        let (share1, share2) = match (movable1, movable2) {
+            (true, true) => {
+                let inverse1 = mass1.copied().unwrap_or_default().recip();
+                let inverse2 = mass2.copied().unwrap_or_default().recip();
+                (inverse1 / (inverse1 + inverse2), inverse2 / (inverse1 + inverse2))
+            }
+            (true, false) => (1.0, 0.0),
+            (false, true) => (0.0, 1.0),
+            (false, false) => continue,
+        };
+        let push = event.manifold.normal * event.manifold.depth;
+        transform1.translation += (push * share1).extend(0.);
+        transform2.translation -= (push * share2).extend(0.);
+    }
+}
+
+/// A group of entities that can be told to collide or not, one per archetype that can collide.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CollisionLayer {
+    Ball,
+    Paddle,
+    Wall,
+    Brick,
+    Capsule,
+    Projectile,
+}
+
+impl CollisionLayer {
+    pub const ALL: [CollisionLayer; 6] = [
+        CollisionLayer::Ball,
+        CollisionLayer::Paddle,
+        CollisionLayer::Wall,
+        CollisionLayer::Brick,
+        CollisionLayer::Capsule,
+        CollisionLayer::Projectile,
+    ];
+
+    pub fn of(entity_type: EntityType) -> Option<CollisionLayer> {
+        match entity_type {
+            EntityType::Ball => Some(CollisionLayer::Ball),
+            EntityType::Paddle => Some(CollisionLayer::Paddle),
+            EntityType::Wall => Some(CollisionLayer::Wall),
+            EntityType::Brick => Some(CollisionLayer::Brick),
+            EntityType::Capsule => Some(CollisionLayer::Capsule),
+            EntityType::Projectile => Some(CollisionLayer::Projectile),
+            EntityType::Score => None,
+        }
+    }
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// Which layers an entity is on, and which it collides with. Two entities only collide when each
+/// one is on a layer the other collides with. Entities without it collide with everything.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionLayers {
+    memberships: u32,
+    filters: u32,
+}
+
+impl CollisionLayers {
+    pub const ALL: CollisionLayers = CollisionLayers { memberships: u32::MAX, filters: u32::MAX };
+
+    pub fn new(memberships: &[CollisionLayer], filters: &[CollisionLayer]) -> CollisionLayers {
+        let mask = |layers: &[CollisionLayer]| layers.iter().fold(0, |mask, layer| mask | layer.bit());
+        CollisionLayers { memberships: mask(memberships), filters: mask(filters) }
+    }
+
+    pub fn interacts_with(&self, other: &CollisionLayers) -> bool {
+        self.memberships & other.filters != 0 && other.memberships & self.filters != 0
+    }
+}
+
+impl Default for CollisionLayers {
+    fn default() -> Self {
+        CollisionLayers::ALL
+    }
+}
+
+// Entities without `CollisionLayers` collide with everything
+fn interacts(layers1: Option<&CollisionLayers>, layers2: Option<&CollisionLayers>) -> bool {
+    layers1.copied().unwrap_or_default().interacts_with(&layers2.copied().unwrap_or_default())
+}
+
+/// The shape an entity collides as, sized by its `transform.scale`. Entities without one are boxes.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColliderShape {
+    #[default]
+    Aabb,
+    /// A circle as wide as the x scale.
+    Circle,
+}
+
+/// A `ColliderShape` placed in the world.
+#[derive(Debug, Clone, Copy)]
+pub enum Bounds {
+    Aabb(Aabb2d),
+    Circle(BoundingCircle),
+}
+
+impl Bounds {
+    pub fn new(shape: Option<&ColliderShape>, transform: &Transform) -> Bounds {
+        let center = transform.translation.truncate();
+        match shape.copied().unwrap_or_default() {
+            ColliderShape::Aabb => Bounds::Aabb(Aabb2d::new(center, transform.scale.truncate() / 2.)),
+            ColliderShape::Circle => Bounds::Circle(BoundingCircle::new(center, transform.scale.x / 2.)),
+        }
+    }
+
+    pub fn center(&self) -> Vec2 {
+        match self {
+            Bounds::Aabb(aabb) => aabb.center(),
+            Bounds::Circle(circle) => circle.center(),
+        }
+    }
+
+    /// The smallest box around `self`.
+    pub fn aabb(&self) -> Aabb2d {
+        match self {
+            Bounds::Aabb(aabb) => *aabb,
+            Bounds::Circle(circle) => circle.aabb_2d(),
+        }
+    }
+
+    pub fn translated_by(self, translation: Vec2) -> Bounds {
+        match self {
+            Bounds::Aabb(aabb) => Bounds::Aabb(aabb.translated_by(translation)),
+            Bounds::Circle(circle) => Bounds::Circle(circle.translated_by(translation)),
+        }
+    }
+
+    // Returns `Some` if `self` collides with `other`.
+    // The returned `Manifold`'s normal points from `other` towards `self`.
+    pub fn contact(&self, other: &Bounds) -> Option<Manifold> {
+        match (*self, *other) {
+            (Bounds::Aabb(bbox1), Bounds::Aabb(bbox2)) => aabb_contact(bbox1, bbox2),
+            (Bounds::Circle(ball), Bounds::Aabb(bounding_box)) => ball_contact(ball, bounding_box),
+            (Bounds::Aabb(bounding_box), Bounds::Circle(ball)) => {
+                ball_contact(ball, bounding_box).map(Manifold::flipped)
+            }
+            (Bounds::Circle(ball1), Bounds::Circle(ball2)) => circle_contact(ball1, ball2),
+        }
+    }
+
+    /// How far `self` can move along `direction`, up to `max`, before it touches `other`.
+    pub fn cast(&self, direction: Dir2, max: f32, other: &Bounds) -> Option<f32> {
+        match (*self, *other) {
+            (Bounds::Aabb(bbox1), Bounds::Aabb(bbox2)) => {
+                AabbCast2d::new(Aabb2d::new(Vec2::ZERO, bbox1.half_size()), bbox1.center(), direction, max)
+                    .aabb_collision_at(bbox2)
+            }
+            (Bounds::Circle(ball), Bounds::Aabb(bounding_box)) => {
+                circle_cast_aabb(&RayCast2d::new(ball.center(), direction, max), ball.radius(), bounding_box)
+            }
+            // A box moving into a circle is the circle moving the other way into the box
+            (Bounds::Aabb(bounding_box), Bounds::Circle(ball)) => {
+                circle_cast_aabb(&RayCast2d::new(ball.center(), -direction, max), ball.radius(), bounding_box)
+            }
+            (Bounds::Circle(ball1), Bounds::Circle(ball2)) => {
+                BoundingCircleCast::new(BoundingCircle::new(Vec2::ZERO, ball1.radius()), ball1.center(), direction, max)
+                    .circle_collision_at(ball2)
+            }
+        }
+    }
+}
+
+// A circle sweeping into a box first touches the box grown by the radius, with rounded corners.
+// That is the box grown sideways, the box grown up and down, and a circle on each corner.
+fn circle_cast_aabb(ray: &RayCast2d, radius: f32, bounding_box: Aabb2d) -> Option<f32> {
+    let wide = Aabb2d {
+        min: bounding_box.min - Vec2::new(radius, 0.),
+        max: bounding_box.max + Vec2::new(radius, 0.),
+    };
+    let tall = Aabb2d {
+        min: bounding_box.min - Vec2::new(0., radius),
+        max: bounding_box.max + Vec2::new(0., radius),
+    };
+    let corners = [
+        bounding_box.min,
+        bounding_box.max,
+        Vec2::new(bounding_box.min.x, bounding_box.max.y),
+        Vec2::new(bounding_box.max.x, bounding_box.min.y),
+    ];
+    [ray.aabb_intersection_at(&wide), ray.aabb_intersection_at(&tall)]
+        .into_iter()
+        .chain(corners.map(|corner| ray.circle_intersection_at(&BoundingCircle::new(corner, radius))))
+        .flatten()
+        .reduce(f32::min)
+}
+
+/// Moves every entity with `Velocity` and a `Collider`, stopping it at the first `Collider` in its way.
+pub fn move_swept(
+    mut mover_query: Query<
+        (Entity, &mut Transform, &Velocity, Option<&ColliderShape>, Option<&CollisionLayers>),
+        With<Collider>,
+    >,
+    obstacle_query: Query<
+        (Entity, &Transform, Option<&ColliderShape>, Option<&CollisionLayers>),
+        (With<Collider>, Without<Velocity>),
+    >,
+    mut tracker: ResMut<CollisionTracker>,
+    time: Res<Time>,
+) {
+    let obstacles: Vec<(Entity, Bounds, Option<&CollisionLayers>)> = obstacle_query
+        .iter()
+        .map(|(entity, transform, shape, layers)| (entity, Bounds::new(shape, transform), layers))
+        .collect();
+    let mut grid = SpatialGrid::default();
+    for (index, (_, obstacle_bounds, _)) in obstacles.iter().enumerate() {
+        grid.insert(index, obstacle_bounds.aabb());
+    }
+    // Movers are swept one after another, so each one is checked against where the earlier
+    // ones ended up and where the later ones started
+    let mut movers: Vec<(Entity, Bounds, Option<CollisionLayers>)> = mover_query
+        .iter()
+        .map(|(entity, transform, _, shape, layers)| (entity, Bounds::new(shape, transform), layers.copied()))
+        .collect();
+
+    for (entity, mut transform, velocity, shape, layers) in &mut mover_query {
+        let displacement = velocity.0 * time.delta_secs();
+        let Ok(direction) = Dir2::new(displacement) else {
+            continue;
+        };
+        let distance = displacement.length();
+        let start = Bounds::new(shape, &transform);
+
+        let swept = start.aabb().merge(&start.translated_by(displacement).aabb());
+        let nearby = grid.query(swept).into_iter().map(|index| obstacles[index]);
+        let other_movers = movers
+            .iter()
+            .filter(|(other, _, _)| *other != entity)
+            .map(|(other, other_bounds, other_layers)| (*other, *other_bounds, other_layers.as_ref()));
+        let mut first_hit: Option<(Entity, f32, Bounds)> = None;
+        for (other, other_bounds, other_layers) in nearby.chain(other_movers) {
+            // Already overlapping at the start of the tick, `check_for_intersections` reports those
+            if !interacts(layers, other_layers) || start.contact(&other_bounds).is_some() {
+                continue;
+            }
+            if let Some(hit_distance) = start.cast(direction, distance, &other_bounds) {
+                if first_hit.is_none_or(|(_, closest, _)| hit_distance < closest) {
+                    first_hit = Some((other, hit_distance, other_bounds));
+                }
+            }
+        }
+
+        let travelled = match first_hit {
+            Some((other, hit_distance, other_bounds)) => {
+                // Nudged just past the contact so the normal is read from a real overlap.
+                // It stops short of it though, so there's nothing to push apart.
+                let contact = start.translated_by(direction * (hit_distance + SKIN));
+                let manifold = contact.contact(&other_bounds).unwrap_or(Manifold {
+                    normal: -direction.as_vec2(),
+                    depth: 0.,
+                    point: contact.center(),
+                });
+                tracker.report(entity, other, Manifold { depth: 0., ..manifold });
+                (hit_distance - SKIN).max(0.0)
+            }
+            None => distance,
+        };
+        let moved = direction * travelled;
+        transform.translation += moved.extend(0.0);
+        if let Some((_, entity_bounds, _)) = movers.iter_mut().find(|(other, _, _)| *other == entity) {
+            *entity_bounds = start.translated_by(moved);
+        }
+    }
+}
+
+/// Reports every pair of overlapping entities to the `CollisionTracker`, see `broadphase` for
+/// which pairs are tested.
+pub fn check_for_intersections(
+    // mut commands: Commands,
+    // mut score: ResMut<Score>,
+    // mut destructor_query: Query<(&mut Velocity, &Transform), With<Destructor>>,
+    // collider_query: Query<(Entity, &Transform, Option<&Destructable>), With<Collider>>,
+    collider_query: Query<
+        (Entity, &Transform, Option<&ColliderShape>, Option<&CollisionLayers>, Has<Velocity>, Has<Controllable>),
+        With<Collider>,
+    >,
+    mut tracker: ResMut<CollisionTracker>,
+) {
+    /*
+    ===Option 1===
+    Entity, Transform, Option<Velocity>, Option<Collider>, Option<Destructor>, Option<Destructable>
+    if not intersecting: continue
+    if destructing each other, add their entity ids to a list
+    if they have colliders and velocity, rebound.
+    afterwards delete entities
+
+    ===Option 2===
+    Check for intersections between all entities, emit an intersection event with both entity ids
+    destruction system: delete the correct entities
+    rebounding system: if not existing, continue
+    I'll try this one because it's more disconnected if anything.
+
+    */
+    // Anything that can move is dynamic, which includes paddles moved by the keyboard
+    let colliders: Vec<(Entity, Bounds, Option<&CollisionLayers>, bool)> = collider_query
+        .iter()
+        .map(|(entity, transform, shape, layers, has_velocity, is_controllable)| {
+            (entity, Bounds::new(shape, transform), layers, has_velocity || is_controllable)
+        })
+        .collect();
+    let boxes: Vec<(Aabb2d, bool)> =
+        colliders.iter().map(|(_, bounds, _, dynamic)| (bounds.aabb(), *dynamic)).collect();
+    for (index1, index2) in broadphase::overlapping_pairs(&boxes) {
+        let (entity1, bounds1, layers1, _) = colliders[index1];
+        let (entity2, bounds2, layers2, _) = colliders[index2];
+        if !interacts(layers1, layers2) {
+            continue;
+        }
+        if let Some(manifold) = bounds1.contact(&bounds2) {
+            // `track_collisions` turns it into an event so that other systems can react to the collision
+            tracker.report(entity1, entity2, manifold);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Collision {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Collision {
+    pub fn opposite(self) -> Collision {
+        match self {
+            Collision::Left => Collision::Right,
+            Collision::Right => Collision::Left,
+            Collision::Top => Collision::Bottom,
+            Collision::Bottom => Collision::Top,
+        }
+    }
+}
+
+/// How two colliding shapes touch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Manifold {
+    /// Points from the second shape towards the first. Moving the first along it separates them.
+    pub normal: Vec2,
+    /// How far the shapes overlap along `normal`.
+    pub depth: f32,
+    /// Roughly where they touch, on or inside both shapes.
+    pub point: Vec2,
+}
+
+impl Manifold {
+    /// The same contact, seen from the second shape.
+    pub fn flipped(self) -> Manifold {
+        Manifold { normal: -self.normal, ..self }
+    }
+
+    /// The side of the second shape that the first hit.
+    pub fn side(&self) -> Collision {
+        side_from_offset(self.normal)
+    }
+}
+
+// The side of something that a collision `offset` away from it hit.
+fn side_from_offset(offset: Vec2) -> Collision {
+    if offset.x.abs() > offset.y.abs() {
+        if offset.x < 0. {
+            Collision::Left
+        } else {
+            Collision::Right
+        }
+    } else if offset.y > 0. {
+        Collision::Top
+    } else {
+        Collision::Bottom
+    }
+}
+
+// Returns `Some` if `bbox1` collides with `bbox2`.
+// They're pushed apart along the axis they overlap least on.
+fn aabb_contact(bbox1: Aabb2d, bbox2: Aabb2d) -> Option<Manifold> {
+    if !bbox1.intersects(&bbox2) {
+        return None;
+    }
+
+    let offset = bbox1.center() - bbox2.center();
+    let overlap = bbox1.half_size() + bbox2.half_size() - offset.abs();
+    // The middle of the overlapping region
+    let point = (bbox1.min.max(bbox2.min) + bbox1.max.min(bbox2.max)) / 2.;
+    let manifold = if overlap.x < overlap.y {
+        Manifold { normal: Vec2::new(offset.x.signum(), 0.), depth: overlap.x, point }
+    } else {
+        Manifold { normal: Vec2::new(0., offset.y.signum()), depth: overlap.y, point }
+    };
+
+    Some(manifold)
+}
+
+// Returns `Some` if `ball` collides with `bounding_box`.
+fn ball_contact(ball: BoundingCircle, bounding_box: Aabb2d) -> Option<Manifold> {
+    if !ball.intersects(&bounding_box) {
+        return None;
+    }
+
+    let closest = bounding_box.closest_point(ball.center());
+    let offset = ball.center() - closest;
+    let distance = offset.length();
+    if distance > 0. {
+        return Some(Manifold { normal: offset / distance, depth: ball.radius() - distance, point: closest });
+    }
+
+    // The center is inside the box, so the ball leaves through the nearest side
+    let to_min = ball.center() - bounding_box.min;
+    let to_max = bounding_box.max - ball.center();
+    let sides = [
+        (to_min.x, Vec2::NEG_X),
+        (to_max.x, Vec2::X),
+        (to_min.y, Vec2::NEG_Y),
+        (to_max.y, Vec2::Y),
+    ];
+    let (to_side, normal) = sides.into_iter().min_by(|(a, _), (b, _)| a.total_cmp(b))?;
+    Some(Manifold { normal, depth: ball.radius() + to_side, point: ball.center() + normal * to_side })
+}
+
+// Returns `Some` if `ball1` collides with `ball2`.
+fn circle_contact(ball1: BoundingCircle, ball2: BoundingCircle) -> Option<Manifold> {
+    if !ball1.intersects(&ball2) {
+        return None;
+    }
+
+    let offset = ball1.center() - ball2.center();
+    let distance = offset.length();
+    // Balls on top of each other are pushed apart vertically
+    let normal = if distance > 0. { offset / distance } else { Vec2::Y };
+    let depth = ball1.radius() + ball2.radius() - distance;
+    Some(Manifold { normal, depth, point: ball2.center() + normal * ball2.radius() })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn world(tick: Duration) -> World {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(tick);
+        world.insert_resource(time);
+        world.init_resource::<CollisionTracker>();
+        world.init_resource::<Events<CollisionEvent>>();
+        world
+    }
+
+    fn sweep(world: &mut World) {
+        world.run_system_once(move_swept).unwrap();
+        world.run_system_once(track_collisions).unwrap();
+    }
+
+    fn ball(world: &mut World, position: Vec2, velocity: Vec2) -> Entity {
+        let transform = Transform::from_translation(position.extend(0.0)).with_scale(Vec3::new(10.0, 10.0, 1.0));
+        world.spawn((transform, Velocity(velocity), Collider)).id()
+    }
+
+    fn obstacle(world: &mut World, position: Vec2, size: Vec2) -> Entity {
+        let transform = Transform::from_translation(position.extend(0.0)).with_scale(size.extend(1.0));
+        world.spawn((transform, Collider)).id()
+    }
+
+    fn sent(world: &World) -> Vec<(Entity, Entity, Collision)> {
+        let events = world.resource::<Events<CollisionEvent>>();
+        events.iter_current_update_events().map(|event| (event.e1, event.e2, event.hit_side_of_e1)).collect()
+    }
+
+    #[test]
+    fn fast_ball_stops_at_a_thin_wall() {
+        let mut world = world(Duration::from_millis(100));
+        let ball = ball(&mut world, Vec2::ZERO, Vec2::new(20_000.0, 0.0));
+        let wall = obstacle(&mut world, Vec2::new(100.0, 0.0), Vec2::new(2.0, 200.0));
+        sweep(&mut world);
+
+        let right_edge = world.get::<Transform>(ball).unwrap().translation.x + 5.0;
+        assert!((98.0..99.0).contains(&right_edge), "ball ended with its edge at {right_edge}");
+        assert_eq!(sent(&world), vec![(ball, wall, Collision::Left)]);
+    }
+
+    #[test]
+    fn long_tick_stops_at_a_brick() {
+        let mut world = world(Duration::from_secs(10));
+        let ball = ball(&mut world, Vec2::ZERO, Vec2::new(0.0, 400.0));
+        let brick = obstacle(&mut world, Vec2::new(0.0, 300.0), Vec2::new(100.0, 30.0));
+        sweep(&mut world);
+
+        let top_edge = world.get::<Transform>(ball).unwrap().translation.y + 5.0;
+        assert!((284.0..285.0).contains(&top_edge), "ball ended with its edge at {top_edge}");
+        assert_eq!(sent(&world), vec![(ball, brick, Collision::Bottom)]);
+    }
+
+    #[test]
+    fn nothing_in_the_way_moves_the_whole_tick() {
+        let mut world = world(Duration::from_secs(1));
+        let ball = ball(&mut world, Vec2::ZERO, Vec2::new(30.0, -40.0));
+        obstacle(&mut world, Vec2::new(0.0, 100.0), Vec2::new(100.0, 30.0));
+        sweep(&mut world);
+
+        let position = world.get::<Transform>(ball).unwrap().translation.truncate();
+        assert!(position.abs_diff_eq(Vec2::new(30.0, -40.0), 1e-4), "ball ended at {position}");
+        assert!(sent(&world).is_empty());
+    }
+
+    /// Runs one tick of contact tracking, returning the phases of the events it sent.
+    fn track(world: &mut World) -> Vec<(Entity, Entity, CollisionPhase)> {
+        world.resource_mut::<Events<CollisionEvent>>().clear();
+        world.run_system_once(check_for_intersections).unwrap();
+        world.run_system_once(track_collisions).unwrap();
+        let events = world.resource::<Events<CollisionEvent>>();
+        events.iter_current_update_events().map(|event| (event.e1, event.e2, event.phase)).collect()
+    }
+
+    #[test]
+    fn contact_starts_persists_and_ends() {
+        let mut world = world(Duration::ZERO);
+        let ball = ball(&mut world, Vec2::ZERO, Vec2::Y);
+        let brick = obstacle(&mut world, Vec2::new(0.0, 8.0), Vec2::new(40.0, 10.0));
+
+        assert_eq!(track(&mut world), vec![(ball, brick, CollisionPhase::Started)]);
+        assert_eq!(track(&mut world), vec![(ball, brick, CollisionPhase::Persisting)]);
+        world.get_mut::<Transform>(ball).unwrap().translation.y = -50.0;
+        assert_eq!(track(&mut world), vec![(ball, brick, CollisionPhase::Ended)]);
+        assert_eq!(track(&mut world), vec![]);
+    }
+
+    #[test]
+    fn despawning_mid_contact_ends_it() {
+        let mut world = world(Duration::ZERO);
+        let ball = ball(&mut world, Vec2::ZERO, Vec2::Y);
+        let brick = obstacle(&mut world, Vec2::new(0.0, 8.0), Vec2::new(40.0, 10.0));
+
+        assert_eq!(track(&mut world), vec![(ball, brick, CollisionPhase::Started)]);
+        world.despawn(brick);
+        assert_eq!(track(&mut world), vec![(ball, brick, CollisionPhase::Ended)]);
+        assert_eq!(track(&mut world), vec![]);
+    }
+}
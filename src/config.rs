@@ -0,0 +1,1052 @@
+//! Archetype definitions and tuning values, loaded from `assets/breakout.ron`.
+//!
+//! Each archetype lists the toggleable components its entities start with, next to its starting
+//! values. Every entity also gets a `Transform` and something to draw it with, a circle mesh for
+//! balls and a `Sprite` for the rest. Fields left out of the file keep their defaults, which match
+//! the original hardcoded game.
+//!
+//! Archetypes that can collide each list the `CollisionLayer`s they collide with in `collides_with`,
+//! see `GameConfig::collision_layers` for how two lists are paired up.
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::{asset::io::file::FileAssetReader, prelude::*};
+use serde::Deserialize;
+
+use crate::{
+    collision::{CollisionLayer, CollisionLayers},
+    powerups::PowerUp,
+    stash::StashableComponent,
+    EntityType,
+};
+
+/// Where `BreakoutPlugin` looks for the config by default, relative to the assets directory.
+pub const DEFAULT_CONFIG_PATH: &str = "breakout.ron";
+
+// These defaults are defined in `Transform` units.
+// Using the default 2D camera they correspond 1:1 with screen pixels.
+const PADDLE_SIZE: [f32; 2] = [120.0, 20.0];
+const GAP_BETWEEN_PADDLE_AND_FLOOR: f32 = 60.0;
+const CONTROLLABLE_SPEED: f32 = 500.0;
+// Degrees above the paddle's surface
+const MIN_BOUNCE_ANGLE: f32 = 30.0;
+const MAX_BOUNCE_ANGLE: f32 = 80.0;
+
+const BALL_DIAMETER: f32 = 30.;
+const BALL_SPEED: f32 = 400.0;
+const INITIAL_BALL_DIRECTION: [f32; 2] = [0.5, 0.5];
+const BALL_DAMAGE: u32 = 1;
+
+const STARTING_LIVES: usize = 3;
+const LEVEL_TRANSITION_SECS: f32 = 2.0;
+
+const WALL_THICKNESS: f32 = 10.0;
+// x coordinates
+const LEFT_WALL: f32 = -450.;
+const RIGHT_WALL: f32 = 450.;
+// y coordinates
+const BOTTOM_WALL: f32 = -300.;
+const TOP_WALL: f32 = 300.;
+
+const BRICK_SIZE: [f32; 2] = [100., 30.];
+// These values are exact
+const GAP_BETWEEN_PADDLE_AND_BRICKS: f32 = 270.0;
+const GAP_BETWEEN_BRICKS: f32 = 5.0;
+// These values are lower bounds, as the number of bricks is computed
+const GAP_BETWEEN_BRICKS_AND_CEILING: f32 = 20.0;
+const GAP_BETWEEN_BRICKS_AND_SIDES: f32 = 20.0;
+const POINTS_PER_BRICK: usize = 1;
+const BRICK_HIT_POINTS: u32 = 1;
+// How much closer to white a brick without `hit_point_colors` gets as it loses all its hit points
+const DAMAGED_BRICK_FADE: f32 = 0.7;
+const TOUGH_BRICK_HIT_POINTS: u32 = 3;
+const TOUGH_BRICK_POINTS: usize = 3;
+// Reaches the bricks next to and diagonal to an explosive one
+const EXPLOSION_RADIUS: f32 = 120.0;
+const REGROW_AFTER_SECS: f32 = 10.0;
+const PATROL_SPEED: f32 = 100.0;
+const PATROL_DISTANCE: f32 = 60.0;
+
+const MAX_COMBO_MULTIPLIER: usize = 8;
+
+const CAPSULE_DROP_CHANCE: f32 = 0.15;
+const CAPSULE_SIZE: [f32; 2] = [40.0, 15.0];
+const CAPSULE_FALL_SPEED: f32 = 150.0;
+const POWER_UP_DURATION_SECS: f32 = 10.0;
+const WIDE_PADDLE_SCALE: f32 = 1.5;
+const SLOW_BALL_SCALE: f32 = 0.6;
+const MULTIBALL_EXTRA_BALLS: usize = 2;
+const MAX_BALLS: usize = 12;
+
+const PROJECTILE_SIZE: [f32; 2] = [6.0, 16.0];
+const PROJECTILE_SPEED: f32 = 600.0;
+const PROJECTILE_LIFETIME_SECS: f32 = 1.5;
+const FIRE_INTERVAL_SECS: f32 = 0.25;
+const MAX_PROJECTILES: usize = 6;
+
+const SCOREBOARD_FONT_SIZE: f32 = 33.0;
+const SCOREBOARD_TEXT_PADDING: f32 = 5.0;
+
+const BACKGROUND_COLOR: [f32; 3] = [0.9, 0.9, 0.9];
+const PADDLE_COLOR: [f32; 3] = [0.3, 0.3, 0.7];
+const BALL_COLOR: [f32; 3] = [1.0, 0.5, 0.5];
+const BRICK_COLOR: [f32; 3] = [0.5, 0.5, 1.0];
+const INDESTRUCTIBLE_BRICK_COLOR: [f32; 3] = [0.4, 0.4, 0.4];
+const EXPLOSIVE_BRICK_COLOR: [f32; 3] = [1.0, 0.6, 0.2];
+const REGENERATING_BRICK_COLOR: [f32; 3] = [0.4, 0.8, 0.4];
+const PATROLLING_BRICK_COLOR: [f32; 3] = [0.7, 0.4, 0.9];
+const TOUGH_BRICK_COLOR: [f32; 3] = [0.8, 0.2, 0.3];
+const WALL_COLOR: [f32; 3] = [0.8, 0.8, 0.8];
+const TEXT_COLOR: [f32; 3] = [0.5, 0.5, 1.0];
+const SCORE_COLOR: [f32; 3] = [1.0, 0.5, 0.5];
+const PROJECTILE_COLOR: [f32; 3] = [0.9, 0.2, 0.2];
+
+/// Everything needed to spawn the game's entities. Colors are sRGB in `0.0..=1.0`.
+#[derive(Resource, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub arena: ArenaConfig,
+    pub paddle: PaddleConfig,
+    pub ball: BallConfig,
+    pub wall: WallConfig,
+    pub brick: BrickConfig,
+    pub power_ups: PowerUpConfig,
+    pub lasers: LaserConfig,
+    pub scoreboard: ScoreboardConfig,
+    pub scoring: ScoringConfig,
+    pub lives: LivesConfig,
+    pub levels: LevelsConfig,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArenaConfig {
+    // x coordinates
+    pub left_wall: f32,
+    pub right_wall: f32,
+    // y coordinates
+    pub bottom_wall: f32,
+    pub top_wall: f32,
+    pub wall_thickness: f32,
+    pub background_color: [f32; 3],
+    /// Whether balls touching the bottom wall are lost, see `lives::LossZone`.
+    pub bottom_is_loss_zone: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaddleConfig {
+    pub components: Vec<StashableComponent>,
+    pub size: [f32; 2],
+    pub gap_to_floor: f32,
+    /// How fast anything `Controllable` moves, not just the paddle.
+    pub speed: f32,
+    pub color: [f32; 3],
+    /// The angle in degrees above the paddle's surface a ball leaves at when it hits an edge.
+    pub min_bounce_angle: f32,
+    /// The angle a ball leaves at when it hits the middle. Anything in between is interpolated.
+    pub max_bounce_angle: f32,
+    /// What paddles touch. Balls bounce off them and capsules are caught by them.
+    pub collides_with: Vec<CollisionLayer>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BallConfig {
+    pub components: Vec<StashableComponent>,
+    pub diameter: f32,
+    pub speed: f32,
+    pub color: [f32; 3],
+    /// One ball is spawned per entry.
+    pub spawns: Vec<BallSpawn>,
+    /// Which way a ball served after losing a life heads.
+    pub serve_direction: [f32; 2],
+    /// How many hit points a ball takes off a brick each time it hits it.
+    pub damage: u32,
+    /// Gives balls a `Mass`, which only matters when they hit something else that moves.
+    pub mass: Option<f32>,
+    /// Gives balls a `Restitution`, which only matters when they hit something else that moves.
+    pub restitution: Option<f32>,
+    /// What balls bounce off.
+    pub collides_with: Vec<CollisionLayer>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BallSpawn {
+    pub position: [f32; 2],
+    /// Only the direction matters, the length comes from `BallConfig::speed`.
+    pub direction: [f32; 2],
+    /// Overrides `BallConfig::color` for this ball.
+    #[serde(default)]
+    pub color: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WallConfig {
+    pub components: Vec<StashableComponent>,
+    pub color: [f32; 3],
+    /// What walls stop.
+    pub collides_with: Vec<CollisionLayer>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrickConfig {
+    pub components: Vec<StashableComponent>,
+    pub size: [f32; 2],
+    // These values are exact
+    pub gap_to_paddle: f32,
+    pub gap_between_bricks: f32,
+    // These values are lower bounds, as the number of bricks is computed
+    pub gap_to_ceiling: f32,
+    pub gap_to_sides: f32,
+    pub color: [f32; 3],
+    /// What destroying a brick is worth, before the combo multiplier.
+    pub points: usize,
+    /// How much damage a brick takes before it's destroyed, see `BallConfig::damage`.
+    pub hit_points: u32,
+    /// The color of a brick with 1, 2, 3... hit points left.
+    /// A brick with more hit points left than there are colors uses `color`, paler the more
+    /// of its hit points it has lost.
+    pub hit_point_colors: Vec<[f32; 3]>,
+    /// Brick types by name, see `bricks`. Each one starts from the plain brick above.
+    pub types: BTreeMap<String, BrickTypeConfig>,
+    /// What can hit a brick.
+    pub collides_with: Vec<CollisionLayer>,
+}
+
+/// The capsules destroyed bricks can drop, and what catching one does, see `powerups`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerUpConfig {
+    pub components: Vec<StashableComponent>,
+    /// The chance, from 0 to 1, that a destroyed brick drops a capsule.
+    pub drop_chance: f32,
+    /// What a dropped capsule can hold, each as likely as the others.
+    pub drops: Vec<PowerUp>,
+    pub capsule_size: [f32; 2],
+    pub fall_speed: f32,
+    /// How many seconds a timed power-up lasts.
+    pub duration: f32,
+    /// How many times wider a wide paddle is.
+    pub wide_paddle_scale: f32,
+    /// What ball speeds are multiplied by while slowed.
+    pub slow_ball_scale: f32,
+    /// How many balls multiball adds for each ball in play.
+    pub multiball_extra_balls: usize,
+    /// Multiball doesn't add balls past this many.
+    pub max_balls: usize,
+    /// What catches a capsule. Capsules that touch nothing fall out of the bottom of the arena.
+    pub collides_with: Vec<CollisionLayer>,
+}
+
+/// The shots paddles fire while the laser power-up is active, see `lasers`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LaserConfig {
+    pub components: Vec<StashableComponent>,
+    pub size: [f32; 2],
+    pub speed: f32,
+    pub color: [f32; 3],
+    /// Seconds a shot that hits nothing lasts.
+    pub lifetime: f32,
+    /// The fewest seconds between two volleys.
+    pub fire_interval: f32,
+    /// Paddles don't fire while this many shots are in flight.
+    pub max_projectiles: usize,
+    /// What a shot is spent on. Leave out `Ball` unless shots should knock balls around, hits between
+    /// moving bodies bounce both of them.
+    pub collides_with: Vec<CollisionLayer>,
+}
+
+/// How a brick type differs from the plain brick. Fields left out are the same as the plain brick's.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrickTypeConfig {
+    /// Leaving out `Destructable` makes the brick indestructible.
+    pub components: Option<Vec<StashableComponent>>,
+    pub color: Option<[f32; 3]>,
+    pub points: Option<usize>,
+    pub hit_points: Option<u32>,
+    /// Destroying the brick also destroys every `Destructable` whose center is this close to its center.
+    pub explosion_radius: Option<f32>,
+    /// Seconds after being destroyed that the brick grows back.
+    pub regrow_after: Option<f32>,
+    pub patrol: Option<PatrolConfig>,
+}
+
+/// Moves a brick back and forth horizontally around where it was placed.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PatrolConfig {
+    pub speed: f32,
+    /// How far it goes either side of where it was placed.
+    pub distance: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoreboardConfig {
+    pub components: Vec<StashableComponent>,
+    pub font_size: f32,
+    pub padding: f32,
+    pub text_color: [f32; 3],
+    pub score_color: [f32; 3],
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoringConfig {
+    /// The most a combo can multiply a brick's points by, see `scoring::Combo`.
+    pub max_combo_multiplier: usize,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LivesConfig {
+    /// Only used when the game starts, a reload doesn't give lives back.
+    pub starting: usize,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LevelsConfig {
+    /// Level files relative to the assets directory, played in order, see `levels`.
+    /// Without any, the game is a single level of bricks filling the grid computed from `brick`.
+    pub files: Vec<PathBuf>,
+    /// Seconds between clearing a level and the next one starting.
+    pub transition: f32,
+}
+
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        ArenaConfig {
+            left_wall: LEFT_WALL,
+            right_wall: RIGHT_WALL,
+            bottom_wall: BOTTOM_WALL,
+            top_wall: TOP_WALL,
+            wall_thickness: WALL_THICKNESS,
+            background_color: BACKGROUND_COLOR,
+            bottom_is_loss_zone: true,
+        }
+    }
+}
+
+impl Default for PaddleConfig {
+    fn default() -> Self {
+        PaddleConfig {
+            components: vec![StashableComponent::Controllable, StashableComponent::Collider],
+            size: PADDLE_SIZE,
+            gap_to_floor: GAP_BETWEEN_PADDLE_AND_FLOOR,
+            speed: CONTROLLABLE_SPEED,
+            color: PADDLE_COLOR,
+            min_bounce_angle: MIN_BOUNCE_ANGLE,
+            max_bounce_angle: MAX_BOUNCE_ANGLE,
+            collides_with: vec![CollisionLayer::Ball, CollisionLayer::Capsule],
+        }
+    }
+}
+
+impl Default for BallConfig {
+    fn default() -> Self {
+        BallConfig {
+            components: vec![
+                StashableComponent::Destructor,
+                StashableComponent::Velocity,
+                StashableComponent::Controllable,
+                StashableComponent::Collider,
+            ],
+            diameter: BALL_DIAMETER,
+            speed: BALL_SPEED,
+            color: BALL_COLOR,
+            spawns: vec![
+                BallSpawn {
+                    position: [0.0, -10.0],
+                    direction: INITIAL_BALL_DIRECTION,
+                    color: None,
+                },
+                BallSpawn {
+                    position: [0.0, -50.0],
+                    direction: INITIAL_BALL_DIRECTION,
+                    color: Some(WALL_COLOR),
+                },
+            ],
+            serve_direction: INITIAL_BALL_DIRECTION,
+            damage: BALL_DAMAGE,
+            mass: None,
+            restitution: None,
+            collides_with: CollisionLayer::ALL.to_vec(),
+        }
+    }
+}
+
+impl Default for WallConfig {
+    fn default() -> Self {
+        WallConfig {
+            components: vec![StashableComponent::Collider],
+            color: WALL_COLOR,
+            collides_with: vec![CollisionLayer::Ball, CollisionLayer::Projectile],
+        }
+    }
+}
+
+impl Default for BrickConfig {
+    fn default() -> Self {
+        BrickConfig {
+            components: vec![StashableComponent::Destructable, StashableComponent::Collider],
+            size: BRICK_SIZE,
+            gap_to_paddle: GAP_BETWEEN_PADDLE_AND_BRICKS,
+            gap_between_bricks: GAP_BETWEEN_BRICKS,
+            gap_to_ceiling: GAP_BETWEEN_BRICKS_AND_CEILING,
+            gap_to_sides: GAP_BETWEEN_BRICKS_AND_SIDES,
+            color: BRICK_COLOR,
+            points: POINTS_PER_BRICK,
+            hit_points: BRICK_HIT_POINTS,
+            hit_point_colors: vec![],
+            types: default_brick_types(),
+            // Bricks never touch each other or the walls
+            collides_with: vec![CollisionLayer::Ball, CollisionLayer::Projectile],
+        }
+    }
+}
+
+impl Default for PowerUpConfig {
+    fn default() -> Self {
+        PowerUpConfig {
+            components: vec![StashableComponent::Velocity, StashableComponent::Collider],
+            drop_chance: CAPSULE_DROP_CHANCE,
+            drops: PowerUp::ALL.to_vec(),
+            capsule_size: CAPSULE_SIZE,
+            fall_speed: CAPSULE_FALL_SPEED,
+            duration: POWER_UP_DURATION_SECS,
+            wide_paddle_scale: WIDE_PADDLE_SCALE,
+            slow_ball_scale: SLOW_BALL_SCALE,
+            multiball_extra_balls: MULTIBALL_EXTRA_BALLS,
+            max_balls: MAX_BALLS,
+            // Capsules are only caught, they fall through everything else
+            collides_with: vec![CollisionLayer::Paddle],
+        }
+    }
+}
+
+impl Default for LaserConfig {
+    fn default() -> Self {
+        LaserConfig {
+            components: vec![
+                StashableComponent::Destructor,
+                StashableComponent::Velocity,
+                StashableComponent::Collider,
+            ],
+            size: PROJECTILE_SIZE,
+            speed: PROJECTILE_SPEED,
+            color: PROJECTILE_COLOR,
+            lifetime: PROJECTILE_LIFETIME_SECS,
+            fire_interval: FIRE_INTERVAL_SECS,
+            max_projectiles: MAX_PROJECTILES,
+            collides_with: vec![CollisionLayer::Wall, CollisionLayer::Brick],
+        }
+    }
+}
+
+// The catalogue every config starts with
+fn default_brick_types() -> BTreeMap<String, BrickTypeConfig> {
+    BTreeMap::from([
+        (
+            "indestructible".to_string(),
+            BrickTypeConfig {
+                components: Some(vec![StashableComponent::Collider]),
+                color: Some(INDESTRUCTIBLE_BRICK_COLOR),
+                points: Some(0),
+                ..default()
+            },
+        ),
+        (
+            "explosive".to_string(),
+            BrickTypeConfig {
+                color: Some(EXPLOSIVE_BRICK_COLOR),
+                explosion_radius: Some(EXPLOSION_RADIUS),
+                ..default()
+            },
+        ),
+        (
+            "regenerating".to_string(),
+            BrickTypeConfig {
+                color: Some(REGENERATING_BRICK_COLOR),
+                regrow_after: Some(REGROW_AFTER_SECS),
+                ..default()
+            },
+        ),
+        (
+            "patrolling".to_string(),
+            BrickTypeConfig {
+                color: Some(PATROLLING_BRICK_COLOR),
+                patrol: Some(PatrolConfig {
+                    speed: PATROL_SPEED,
+                    distance: PATROL_DISTANCE,
+                }),
+                ..default()
+            },
+        ),
+        (
+            "tough".to_string(),
+            BrickTypeConfig {
+                color: Some(TOUGH_BRICK_COLOR),
+                points: Some(TOUGH_BRICK_POINTS),
+                hit_points: Some(TOUGH_BRICK_HIT_POINTS),
+                ..default()
+            },
+        ),
+    ])
+}
+
+impl Default for ScoreboardConfig {
+    fn default() -> Self {
+        ScoreboardConfig {
+            components: vec![],
+            font_size: SCOREBOARD_FONT_SIZE,
+            padding: SCOREBOARD_TEXT_PADDING,
+            text_color: TEXT_COLOR,
+            score_color: SCORE_COLOR,
+        }
+    }
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        ScoringConfig {
+            max_combo_multiplier: MAX_COMBO_MULTIPLIER,
+        }
+    }
+}
+
+impl Default for LivesConfig {
+    fn default() -> Self {
+        LivesConfig {
+            starting: STARTING_LIVES,
+        }
+    }
+}
+
+impl Default for LevelsConfig {
+    fn default() -> Self {
+        LevelsConfig {
+            files: vec![],
+            transition: LEVEL_TRANSITION_SECS,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    /// Every problem found, so they can all be fixed in one go.
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "couldn't read the file: {error}"),
+            ConfigError::Parse(error) => write!(f, "couldn't parse the file: {error}"),
+            ConfigError::Invalid(problems) => {
+                write!(f, "invalid values:")?;
+                for problem in problems {
+                    write!(f, "\n  - {problem}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The full path of a config, given its path relative to the assets directory.
+pub fn asset_path(path: &Path) -> PathBuf {
+    FileAssetReader::get_base_path().join("assets").join(path)
+}
+
+/// Loads the config at `path`, relative to the assets directory.
+/// Falls back to the defaults if the file doesn't exist, or logs why it's unusable and does the same.
+pub fn load_or_default(path: &Path) -> GameConfig {
+    let full_path = asset_path(path);
+    match GameConfig::load(&full_path) {
+        Ok(config) => config,
+        Err(ConfigError::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+            warn!("{} doesn't exist, using the default config", full_path.display());
+            GameConfig::default()
+        }
+        Err(error) => {
+            error!("{}: {error}\nUsing the default config instead.", full_path.display());
+            GameConfig::default()
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn load(path: &Path) -> Result<GameConfig, ConfigError> {
+        let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        GameConfig::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<GameConfig, ConfigError> {
+        let config: GameConfig = ron::from_str(text).map_err(ConfigError::Parse)?;
+        config.validate().map_err(ConfigError::Invalid)?;
+        Ok(config)
+    }
+
+    /// Checks the values that would otherwise make spawning panic or misbehave.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = vec![];
+        let mut check = |ok: bool, problem: String| {
+            if !ok {
+                problems.push(problem);
+            }
+        };
+        // Comparisons are written so that NaN fails them.
+        let arena = &self.arena;
+        check(
+            arena.right_wall > arena.left_wall,
+            format!(
+                "arena.right_wall ({}) must be greater than arena.left_wall ({})",
+                arena.right_wall, arena.left_wall
+            ),
+        );
+        check(
+            arena.top_wall > arena.bottom_wall,
+            format!(
+                "arena.top_wall ({}) must be greater than arena.bottom_wall ({})",
+                arena.top_wall, arena.bottom_wall
+            ),
+        );
+        check(
+            arena.wall_thickness > 0.0,
+            format!("arena.wall_thickness ({}) must be positive", arena.wall_thickness),
+        );
+
+        let paddle = &self.paddle;
+        check_size(&mut check, "paddle.size", paddle.size);
+        check(
+            paddle.size[0] < self.inner_width(),
+            format!(
+                "paddle.size ({}) must be narrower than the inside of the arena ({})",
+                paddle.size[0],
+                self.inner_width()
+            ),
+        );
+        check(
+            paddle.gap_to_floor > 0.0 && self.paddle_y() < arena.top_wall,
+            format!(
+                "paddle.gap_to_floor ({}) must put the paddle inside the arena",
+                paddle.gap_to_floor
+            ),
+        );
+        check(
+            paddle.speed >= 0.0,
+            format!("paddle.speed ({}) can't be negative", paddle.speed),
+        );
+        check(
+            0.0 < paddle.min_bounce_angle
+                && paddle.min_bounce_angle <= paddle.max_bounce_angle
+                && paddle.max_bounce_angle <= 90.0,
+            format!(
+                "paddle.min_bounce_angle ({}) and paddle.max_bounce_angle ({}) must be in order, between 0 and 90 degrees",
+                paddle.min_bounce_angle, paddle.max_bounce_angle
+            ),
+        );
+
+        let ball = &self.ball;
+        check(
+            ball.diameter > 0.0,
+            format!("ball.diameter ({}) must be positive", ball.diameter),
+        );
+        check(
+            ball.speed >= 0.0,
+            format!("ball.speed ({}) can't be negative", ball.speed),
+        );
+        for (i, spawn) in ball.spawns.iter().enumerate() {
+            let direction = Vec2::from(spawn.direction);
+            check(
+                direction.is_finite() && direction != Vec2::ZERO,
+                format!("ball.spawns[{i}].direction ({direction}) must be a non-zero vector"),
+            );
+            check(
+                self.contains(Vec2::from(spawn.position)),
+                format!(
+                    "ball.spawns[{i}].position ({}) must be inside the arena",
+                    Vec2::from(spawn.position)
+                ),
+            );
+        }
+
+        let serve_direction = Vec2::from(ball.serve_direction);
+        check(
+            serve_direction.is_finite() && serve_direction != Vec2::ZERO,
+            format!("ball.serve_direction ({serve_direction}) must be a non-zero vector"),
+        );
+        check(ball.damage > 0, "ball.damage must be at least 1".to_string());
+        if let Some(mass) = ball.mass {
+            check(mass > 0.0, format!("ball.mass ({mass}) must be positive"));
+        }
+        if let Some(restitution) = ball.restitution {
+            check(
+                (0.0..=1.0).contains(&restitution),
+                format!("ball.restitution ({restitution}) must be between 0 and 1"),
+            );
+        }
+
+        let brick = &self.brick;
+        check_size(&mut check, "brick.size", brick.size);
+        for (name, gap) in [
+            ("brick.gap_to_paddle", brick.gap_to_paddle),
+            ("brick.gap_between_bricks", brick.gap_between_bricks),
+            ("brick.gap_to_ceiling", brick.gap_to_ceiling),
+            ("brick.gap_to_sides", brick.gap_to_sides),
+        ] {
+            check(gap >= 0.0, format!("{name} ({gap}) can't be negative"));
+        }
+        check(brick.hit_points > 0, "brick.hit_points must be at least 1".to_string());
+        for (name, brick_type) in &brick.types {
+            if let Some(hit_points) = brick_type.hit_points {
+                check(hit_points > 0, format!("brick.types[{name:?}].hit_points must be at least 1"));
+            }
+            if let Some(radius) = brick_type.explosion_radius {
+                check(
+                    radius > 0.0,
+                    format!("brick.types[{name:?}].explosion_radius ({radius}) must be positive"),
+                );
+            }
+            if let Some(delay) = brick_type.regrow_after {
+                check(
+                    delay >= 0.0,
+                    format!("brick.types[{name:?}].regrow_after ({delay}) can't be negative"),
+                );
+            }
+            if let Some(patrol) = &brick_type.patrol {
+                check(
+                    patrol.speed >= 0.0 && patrol.distance >= 0.0,
+                    format!(
+                        "brick.types[{name:?}].patrol speed ({}) and distance ({}) can't be negative",
+                        patrol.speed, patrol.distance
+                    ),
+                );
+            }
+        }
+        let (n_rows, n_columns) = self.brick_grid_size();
+        check(
+            n_columns > 0,
+            "brick.size, brick.gap_between_bricks and brick.gap_to_sides leave no room for a column of bricks".to_string(),
+        );
+        check(
+            n_rows > 0,
+            "brick.size, brick.gap_to_paddle and brick.gap_to_ceiling leave no room for a row of bricks".to_string(),
+        );
+
+        let power_ups = &self.power_ups;
+        check(
+            (0.0..=1.0).contains(&power_ups.drop_chance),
+            format!("power_ups.drop_chance ({}) must be between 0 and 1", power_ups.drop_chance),
+        );
+        check_size(&mut check, "power_ups.capsule_size", power_ups.capsule_size);
+        check(
+            power_ups.fall_speed > 0.0,
+            format!("power_ups.fall_speed ({}) must be positive", power_ups.fall_speed),
+        );
+        check(
+            power_ups.duration > 0.0,
+            format!("power_ups.duration ({}) must be positive", power_ups.duration),
+        );
+        check(
+            power_ups.wide_paddle_scale > 0.0 && power_ups.slow_ball_scale > 0.0,
+            format!(
+                "power_ups.wide_paddle_scale ({}) and power_ups.slow_ball_scale ({}) must be positive",
+                power_ups.wide_paddle_scale, power_ups.slow_ball_scale
+            ),
+        );
+        let wide_paddle = self.paddle.size[0] * power_ups.wide_paddle_scale;
+        check(
+            wide_paddle < self.inner_width(),
+            format!(
+                "paddle.size ({}) times power_ups.wide_paddle_scale ({}) must be narrower than the inside of the arena ({})",
+                self.paddle.size[0],
+                power_ups.wide_paddle_scale,
+                self.inner_width()
+            ),
+        );
+
+        let lasers = &self.lasers;
+        check_size(&mut check, "lasers.size", lasers.size);
+        for (name, value) in [
+            ("lasers.speed", lasers.speed),
+            ("lasers.lifetime", lasers.lifetime),
+            ("lasers.fire_interval", lasers.fire_interval),
+        ] {
+            check(value > 0.0, format!("{name} ({value}) must be positive"));
+        }
+
+        check(
+            self.scoring.max_combo_multiplier > 0,
+            "scoring.max_combo_multiplier must be at least 1".to_string(),
+        );
+
+        check(
+            self.lives.starting > 0,
+            "lives.starting must be at least 1".to_string(),
+        );
+        check(
+            self.levels.transition >= 0.0,
+            format!("levels.transition ({}) can't be negative", self.levels.transition),
+        );
+
+        check(
+            self.scoreboard.font_size > 0.0,
+            format!("scoreboard.font_size ({}) must be positive", self.scoreboard.font_size),
+        );
+
+        let mut colors = vec![
+            ("arena.background_color".to_string(), arena.background_color),
+            ("paddle.color".to_string(), paddle.color),
+            ("ball.color".to_string(), ball.color),
+            ("wall.color".to_string(), self.wall.color),
+            ("brick.color".to_string(), brick.color),
+            ("lasers.color".to_string(), lasers.color),
+            ("scoreboard.text_color".to_string(), self.scoreboard.text_color),
+            ("scoreboard.score_color".to_string(), self.scoreboard.score_color),
+        ];
+        for (i, spawn) in ball.spawns.iter().enumerate() {
+            colors.extend(spawn.color.map(|color| (format!("ball.spawns[{i}].color"), color)));
+        }
+        for (i, &color) in brick.hit_point_colors.iter().enumerate() {
+            colors.push((format!("brick.hit_point_colors[{i}]"), color));
+        }
+        for (name, brick_type) in &brick.types {
+            colors.extend(brick_type.color.map(|color| (format!("brick.types[{name:?}].color"), color)));
+        }
+        for (name, color) in colors {
+            check(
+                color.iter().all(|channel| (0.0..=1.0).contains(channel)),
+                format!("{name} ({color:?}) must have every channel between 0.0 and 1.0"),
+            );
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// The components an archetype's entities start with, besides how they're drawn and their `Transform`.
+    pub fn components(&self, entity_type: EntityType) -> &[StashableComponent] {
+        match entity_type {
+            EntityType::Ball => &self.ball.components,
+            EntityType::Paddle => &self.paddle.components,
+            EntityType::Wall => &self.wall.components,
+            EntityType::Brick => &self.brick.components,
+            EntityType::Capsule => &self.power_ups.components,
+            EntityType::Projectile => &self.lasers.components,
+            EntityType::Score => &self.scoreboard.components,
+        }
+    }
+
+    /// The layers an archetype's entities are on and collide with, for those that can collide.
+    /// Each archetype is on its own layer and lists the layers it collides with in its section.
+    /// A pair only collides when both sides list each other.
+    pub fn collision_layers(&self, entity_type: EntityType) -> Option<CollisionLayers> {
+        let collides_with = match entity_type {
+            EntityType::Ball => &self.ball.collides_with,
+            EntityType::Paddle => &self.paddle.collides_with,
+            EntityType::Wall => &self.wall.collides_with,
+            EntityType::Brick => &self.brick.collides_with,
+            EntityType::Capsule => &self.power_ups.collides_with,
+            EntityType::Projectile => &self.lasers.collides_with,
+            EntityType::Score => return None,
+        };
+        let layer = CollisionLayer::of(entity_type)?;
+        Some(CollisionLayers::new(&[layer], collides_with))
+    }
+
+    /// The color a brick of `brick_type` with `hit_points` left is drawn in.
+    /// A type's color takes the place of `BrickConfig::color`, `hit_point_colors` still win over it.
+    pub fn brick_color(&self, brick_type: Option<&str>, hit_points: u32) -> Color {
+        let brick = &self.brick;
+        let type_config = brick_type.and_then(|name| brick.types.get(name));
+        let stage_color = (hit_points as usize)
+            .checked_sub(1)
+            .and_then(|index| brick.hit_point_colors.get(index));
+        if let Some(&rgb) = stage_color {
+            return color(rgb);
+        }
+        let rgb = type_config.and_then(|brick_type| brick_type.color).unwrap_or(brick.color);
+        let full_hit_points = type_config
+            .and_then(|brick_type| brick_type.hit_points)
+            .unwrap_or(brick.hit_points);
+        let lost = 1.0 - (hit_points as f32 / full_hit_points as f32).min(1.0);
+        color(rgb.map(|channel| channel + (1.0 - channel) * lost * DAMAGED_BRICK_FADE))
+    }
+
+    pub fn paddle_y(&self) -> f32 {
+        self.arena.bottom_wall + self.paddle.gap_to_floor
+    }
+
+    /// Width between the inner faces of the side walls.
+    fn inner_width(&self) -> f32 {
+        self.arena.right_wall - self.arena.left_wall - self.arena.wall_thickness
+    }
+
+    pub(crate) fn contains(&self, position: Vec2) -> bool {
+        position.x > self.arena.left_wall
+            && position.x < self.arena.right_wall
+            && position.y > self.arena.bottom_wall
+            && position.y < self.arena.top_wall
+    }
+
+    /// How many rows and columns of bricks fit in the space available.
+    pub(crate) fn brick_grid_size(&self) -> (usize, usize) {
+        let arena = &self.arena;
+        let brick = &self.brick;
+        let total_width_of_bricks = (arena.right_wall - arena.left_wall) - 2. * brick.gap_to_sides;
+        let bottom_edge_of_bricks = self.paddle_y() + brick.gap_to_paddle;
+        let total_height_of_bricks = arena.top_wall - bottom_edge_of_bricks - brick.gap_to_ceiling;
+
+        // `as usize` saturates, so negative space or a NaN gives zero bricks
+        let n_columns = (total_width_of_bricks / (brick.size[0] + brick.gap_between_bricks)).floor() as usize;
+        let n_rows = (total_height_of_bricks / (brick.size[1] + brick.gap_between_bricks)).floor() as usize;
+        (n_rows, n_columns)
+    }
+
+    /// Centers of every brick, row by row from the bottom.
+    pub fn brick_positions(&self) -> Vec<Vec2> {
+        let arena = &self.arena;
+        let brick = &self.brick;
+        let brick_size = Vec2::from(brick.size);
+        let (n_rows, n_columns) = self.brick_grid_size();
+        if n_rows == 0 || n_columns == 0 {
+            return vec![];
+        }
+        let n_vertical_gaps = n_columns - 1;
+        let bottom_edge_of_bricks = self.paddle_y() + brick.gap_to_paddle;
+
+        // Because we need to round the number of columns,
+        // the space on the top and sides of the bricks only captures a lower bound, not an exact value
+        let center_of_bricks = (arena.left_wall + arena.right_wall) / 2.0;
+        let left_edge_of_bricks = center_of_bricks
+            // Space taken up by the bricks
+            - (n_columns as f32 / 2.0 * brick_size.x)
+            // Space taken up by the gaps
+            - n_vertical_gaps as f32 / 2.0 * brick.gap_between_bricks;
+
+        // In Bevy, the `translation` of an entity describes the center point,
+        // not its bottom-left corner
+        let offset_x = left_edge_of_bricks + brick_size.x / 2.;
+        let offset_y = bottom_edge_of_bricks + brick_size.y / 2.;
+
+        let mut positions = Vec::with_capacity(n_rows * n_columns);
+        for row in 0..n_rows {
+            for column in 0..n_columns {
+                positions.push(Vec2::new(
+                    offset_x + column as f32 * (brick_size.x + brick.gap_between_bricks),
+                    offset_y + row as f32 * (brick_size.y + brick.gap_between_bricks),
+                ));
+            }
+        }
+        positions
+    }
+}
+
+fn check_size(check: &mut impl FnMut(bool, String), name: &str, size: [f32; 2]) {
+    check(
+        size[0] > 0.0 && size[1] > 0.0,
+        format!("{name} ({size:?}) must be positive in both dimensions"),
+    );
+}
+
+/// An sRGB color from the config.
+pub fn color(rgb: [f32; 3]) -> Color {
+    Color::srgb_from_array(rgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(text: &str) -> Vec<String> {
+        match GameConfig::parse(text) {
+            Err(ConfigError::Invalid(problems)) => problems,
+            other => panic!("expected invalid values, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn shipped_config_parses() {
+        let mut config = GameConfig::parse(include_str!("../assets/breakout.ron")).unwrap();
+        assert!(!config.levels.files.is_empty());
+        // Everything but the level list matches the defaults
+        config.levels.files.clear();
+        assert_eq!(config, GameConfig::default());
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        assert!(matches!(GameConfig::parse("(arena: (left_wal: -450.0))"), Err(ConfigError::Parse(_))));
+        assert!(matches!(GameConfig::parse("(paddles: ())"), Err(ConfigError::Parse(_))));
+    }
+
+    #[test]
+    fn inverted_arena_reports_every_problem() {
+        let problems = problems("(arena: (left_wall: 450.0, right_wall: -450.0, top_wall: -300.0, bottom_wall: 300.0))");
+        assert!(problems.iter().any(|problem| problem.starts_with("arena.right_wall")));
+        assert!(problems.iter().any(|problem| problem.starts_with("arena.top_wall")));
+        assert!(problems.iter().any(|problem| problem.contains("no room for a column")));
+    }
+
+    #[test]
+    fn empty_brick_grid_reports_every_problem() {
+        let problems = problems("(brick: (size: (0.0, 30.0), gap_to_sides: 1000.0, gap_to_paddle: 1000.0))");
+        assert!(problems.iter().any(|problem| problem.starts_with("brick.size")));
+        assert!(problems.iter().any(|problem| problem.contains("no room for a column")));
+        assert!(problems.iter().any(|problem| problem.contains("no room for a row")));
+    }
+
+    #[test]
+    fn damaged_bricks_fade_without_stage_colors() {
+        let mut config = GameConfig::default();
+        let full = config.brick_color(Some("tough"), 3).to_srgba();
+        let damaged = config.brick_color(Some("tough"), 1).to_srgba();
+        assert_eq!(full, color(TOUGH_BRICK_COLOR).to_srgba());
+        assert!(damaged.red > full.red && damaged.green > full.green && damaged.blue > full.blue);
+
+        config.brick.hit_point_colors = vec![[0.0, 0.0, 0.0]];
+        assert_eq!(config.brick_color(Some("tough"), 1), color([0.0, 0.0, 0.0]));
+        // Stages past the listed colors still fade
+        let two_left = config.brick_color(Some("tough"), 2).to_srgba();
+        assert!(two_left.red > full.red && two_left.red < damaged.red);
+    }
+
+    #[test]
+    fn wide_paddle_has_to_fit_the_arena() {
+        let problems = problems("(paddle: (size: (120.0, 20.0)), power_ups: (wide_paddle_scale: 10.0))");
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("power_ups.wide_paddle_scale"));
+    }
+
+    #[test]
+    fn every_color_is_range_checked() {
+        let problems = problems(
+            r#"(
+                brick: (hit_point_colors: [(0.5, 0.5, 0.5), (0.5, 1.5, 0.5)], types: {"glowing": (color: Some((2.0, 0.0, 0.0)))}),
+                lasers: (color: (0.9, -0.2, 0.2)),
+            )"#,
+        );
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        for name in ["lasers.color", "brick.hit_point_colors[1]", "brick.types[\"glowing\"].color"] {
+            assert!(problems.iter().any(|problem| problem.starts_with(name)), "{name} in {problems:?}");
+        }
+    }
+}
@@ -0,0 +1,460 @@
+//! An in-game level editor, opened with E from the main menu.
+//!
+//! The editor draws the arena walls and a snap grid with a cell for every brick a level can hold,
+//! see `levels`, along with where the paddle and ball start.
+//! - Left click paints the selected brick type, replacing whatever was in the cell, and right
+//!   click erases. Holding a button paints or erases every cell the cursor passes over.
+//! - Tab cycles through the brick types, a plain `brick` first and then `BrickConfig::types`.
+//! - P moves the paddle's start under the cursor, and B moves the ball's.
+//! - PageUp and PageDown open the previous and next of `LevelsConfig::files`, dropping any edits.
+//! - S saves the level over its file, Enter play-tests it and Backspace goes back to the menu.
+//!
+//! A play-test is a game of just the edited level, saved or not. Pressing Enter again, or the game
+//! ending either way, comes back to the editor with the edits as they were.
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    config::{asset_path, color, GameConfig},
+    levels::{cell_at, cell_center, load_level, Level, LevelError, PLAIN_BRICK},
+    states::{GameState, InGame},
+    BallMesh, WallBundle, WallLocation,
+};
+
+pub const EDITOR_KEY: KeyCode = KeyCode::KeyE;
+const PAINT_BUTTON: MouseButton = MouseButton::Left;
+const ERASE_BUTTON: MouseButton = MouseButton::Right;
+const BRICK_TYPE_KEY: KeyCode = KeyCode::Tab;
+const PADDLE_KEY: KeyCode = KeyCode::KeyP;
+const BALL_KEY: KeyCode = KeyCode::KeyB;
+const PREVIOUS_LEVEL_KEY: KeyCode = KeyCode::PageUp;
+const NEXT_LEVEL_KEY: KeyCode = KeyCode::PageDown;
+const SAVE_KEY: KeyCode = KeyCode::KeyS;
+/// Starts a play-test from the editor, and ends it again.
+pub const PLAY_TEST_KEY: KeyCode = KeyCode::Enter;
+// Not Escape, which the sandbox binary quits on
+const MENU_KEY: KeyCode = KeyCode::Backspace;
+
+/// Where the editor saves when `LevelsConfig::files` is empty, relative to the assets directory.
+pub const NEW_LEVEL_PATH: &str = "levels/new.level";
+const NEW_LEVEL_NAME: &str = "Untitled";
+
+// Empty cells are a faint brick, start positions a see-through paddle and ball
+const GRID_ALPHA: f32 = 0.15;
+const MARKER_ALPHA: f32 = 0.6;
+const GRID_Z: f32 = -1.0;
+const MARKER_Z: f32 = 1.0;
+const HELP_FONT_SIZE_SCALE: f32 = 0.5;
+
+/// The level being edited. It outlives play-tests, so they don't lose unsaved edits.
+#[derive(Resource, Debug, Clone)]
+pub struct EditorLevel {
+    /// Which of `LevelsConfig::files` this is.
+    pub index: usize,
+    /// Where it's saved, relative to the assets directory.
+    pub path: PathBuf,
+    pub name: String,
+    pub author: Option<String>,
+    /// How many columns the level is edited and saved with, see `open`.
+    pub columns: usize,
+    /// Brick types by `[row][column]`, as many rows as fit the computed brick grid. `None` is empty.
+    pub cells: Vec<Vec<Option<String>>>,
+    pub paddle_x: Option<f32>,
+    pub ball: Option<Vec2>,
+    /// Which of `palette` left click paints.
+    pub selected: usize,
+    /// How the last save went, or why the level can't be saved or played.
+    pub status: String,
+}
+
+/// Holds the edited level while it's being play-tested.
+#[derive(Resource, Debug, Default)]
+pub struct PlayTest(pub Option<Level>);
+
+#[derive(Component)]
+struct EditorSprite;
+
+#[derive(Component)]
+struct EditorHud;
+
+impl EditorLevel {
+    /// Opens the level at `index` of `LevelsConfig::files`, or an empty one without a file that loads.
+    ///
+    /// Levels are centered, so one whose width is odd when the computed brick grid's is even, or
+    /// the other way around, sits half a cell off that grid. It's edited one column narrower
+    /// instead, where its bricks line up with the cells and stay put when it's saved.
+    pub fn open(config: &GameConfig, index: usize) -> EditorLevel {
+        let (n_rows, n_columns) = config.brick_grid_size();
+        let level = load_level(config, index);
+        let columns = match &level {
+            Some(level) if level.width() % 2 != n_columns % 2 => n_columns - 1,
+            _ => n_columns,
+        };
+        let mut editor_level = EditorLevel {
+            index,
+            path: config
+                .levels
+                .files
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| PathBuf::from(NEW_LEVEL_PATH)),
+            name: NEW_LEVEL_NAME.to_string(),
+            author: None,
+            columns,
+            cells: vec![vec![None; columns]; n_rows],
+            paddle_x: None,
+            ball: None,
+            selected: 0,
+            status: String::new(),
+        };
+        let Some(level) = level else {
+            return editor_level;
+        };
+        for (position, brick_type) in level.brick_positions(config) {
+            if let Some((row, column)) = cell_at(config, columns, position) {
+                editor_level.cells[row][column] = Some(brick_type.unwrap_or(PLAIN_BRICK).to_string());
+            }
+        }
+        editor_level.name = level.name;
+        editor_level.author = level.author;
+        editor_level.paddle_x = level.paddle_x;
+        editor_level.ball = level.ball;
+        editor_level
+    }
+
+    /// The level as it would be saved.
+    pub fn to_level(&self) -> Level {
+        let mut legend = BTreeMap::new();
+        let mut rows = vec![];
+        for cells in &self.cells {
+            let row: Vec<char> = cells
+                .iter()
+                .map(|cell| cell.as_deref().map_or('.', |brick_type| legend_char(&mut legend, brick_type)))
+                .collect();
+            rows.push(row);
+        }
+        // Rows keep their full width, so the level isn't recentered when it's loaded
+        while rows.last().is_some_and(|row| row.iter().all(|cell| *cell == '.')) {
+            rows.pop();
+        }
+        Level {
+            name: self.name.clone(),
+            author: self.author.clone(),
+            paddle_x: self.paddle_x,
+            ball: self.ball,
+            legend,
+            rows,
+        }
+    }
+}
+
+// A brick type's initial when it's free, so saved legends stay readable
+fn legend_char(legend: &mut BTreeMap<char, String>, brick_type: &str) -> char {
+    if let Some((cell, _)) = legend.iter().find(|(_, other)| *other == brick_type) {
+        return *cell;
+    }
+    let initial = brick_type.chars().next().map(|initial| initial.to_ascii_uppercase());
+    let cell = initial
+        .into_iter()
+        .chain('A'..='Z')
+        .chain('!'..)
+        .find(|cell| {
+            !cell.is_whitespace() && !cell.is_control() && !matches!(cell, '.' | '#' | ':') && !legend.contains_key(cell)
+        })
+        .unwrap_or('?');
+    legend.insert(cell, brick_type.to_string());
+    cell
+}
+
+/// The brick types the editor can paint, in the order Tab goes through them.
+pub fn palette(config: &GameConfig) -> Vec<&str> {
+    let mut palette = vec![PLAIN_BRICK];
+    palette.extend(config.brick.types.keys().map(String::as_str));
+    palette
+}
+
+/// The level a new game starts with: the one being play-tested, otherwise the first level file.
+pub(crate) fn starting_level(config: &GameConfig, play_test: &PlayTest) -> Option<Level> {
+    play_test.0.clone().or_else(|| load_level(config, 0))
+}
+
+pub(crate) fn add_editor(app: &mut App) {
+    app.init_resource::<PlayTest>()
+        .add_systems(OnEnter(GameState::Editor), (end_play_test, spawn_editor, draw_level).chain())
+        .add_systems(
+            Update,
+            (
+                open_editor.run_if(in_state(GameState::MainMenu)),
+                leave_play_test.run_if(in_state(InGame)),
+                (
+                    switch_level,
+                    edit_level,
+                    use_level,
+                    draw_level.run_if(resource_exists_and_changed::<EditorLevel>),
+                    update_editor_hud,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Editor)),
+            ),
+        );
+}
+
+fn open_editor(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    config: Res<GameConfig>,
+) {
+    if keyboard_input.just_pressed(EDITOR_KEY) {
+        commands.insert_resource(EditorLevel::open(&config, 0));
+        next_state.set(GameState::Editor);
+    }
+}
+
+fn end_play_test(mut play_test: ResMut<PlayTest>) {
+    play_test.0 = None;
+}
+
+fn leave_play_test(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    play_test: Res<PlayTest>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if play_test.0.is_some() && keyboard_input.just_pressed(PLAY_TEST_KEY) {
+        next_state.set(GameState::Editor);
+    }
+}
+
+// The walls don't change while editing, unlike the rest of what's drawn
+fn spawn_editor(mut commands: Commands, config: Res<GameConfig>) {
+    for location in WallLocation::ALL {
+        commands.spawn((WallBundle::new(location, &config), StateScoped(GameState::Editor)));
+    }
+    let scoreboard = &config.scoreboard;
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: scoreboard.font_size * HELP_FONT_SIZE_SCALE,
+            ..default()
+        },
+        TextColor(color(scoreboard.text_color)),
+        EditorHud,
+        StateScoped(GameState::Editor),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(scoreboard.padding),
+            left: Val::Px(scoreboard.padding),
+            ..default()
+        },
+    ));
+}
+
+fn draw_level(
+    mut commands: Commands,
+    editor_level: Res<EditorLevel>,
+    sprite_query: Query<Entity, With<EditorSprite>>,
+    ball_mesh: Res<BallMesh>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    config: Res<GameConfig>,
+) {
+    for entity in &sprite_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    let brick = &config.brick;
+    let brick_size = Vec2::from(brick.size).extend(1.0);
+    for (row, cells) in editor_level.cells.iter().enumerate() {
+        for (column, cell) in cells.iter().enumerate() {
+            let position = cell_center(&config, editor_level.columns, row, column);
+            let (sprite_color, z) = match cell.as_deref() {
+                None => (color(brick.color).with_alpha(GRID_ALPHA), GRID_Z),
+                Some(brick_type) => {
+                    let brick_type = (brick_type != PLAIN_BRICK).then_some(brick_type);
+                    let hit_points = brick_type
+                        .and_then(|name| brick.types.get(name))
+                        .and_then(|type_config| type_config.hit_points)
+                        .unwrap_or(brick.hit_points);
+                    (config.brick_color(brick_type, hit_points), 0.0)
+                }
+            };
+            commands.spawn((
+                Sprite::from_color(sprite_color, Vec2::ONE),
+                Transform::from_translation(position.extend(z)).with_scale(brick_size),
+                EditorSprite,
+                StateScoped(GameState::Editor),
+            ));
+        }
+    }
+
+    let paddle = &config.paddle;
+    let paddle_x = editor_level.paddle_x.unwrap_or(0.0);
+    commands.spawn((
+        Sprite::from_color(color(paddle.color).with_alpha(MARKER_ALPHA), Vec2::ONE),
+        Transform::from_xyz(paddle_x, config.paddle_y(), MARKER_Z).with_scale(Vec2::from(paddle.size).extend(1.0)),
+        EditorSprite,
+        StateScoped(GameState::Editor),
+    ));
+    // Without a start of their own, levels serve the config's balls
+    let ball = &config.ball;
+    let balls: Vec<Vec2> = match editor_level.ball {
+        Some(position) => vec![position],
+        None => ball.spawns.iter().map(|spawn| Vec2::from(spawn.position)).collect(),
+    };
+    for position in balls {
+        commands.spawn((
+            Mesh2d(ball_mesh.0.clone()),
+            MeshMaterial2d(materials.add(color(ball.color).with_alpha(MARKER_ALPHA))),
+            Transform::from_translation(position.extend(MARKER_Z)).with_scale(Vec2::splat(ball.diameter).extend(1.0)),
+            EditorSprite,
+            StateScoped(GameState::Editor),
+        ));
+    }
+}
+
+// Where the cursor is in the world, if it's over the window
+fn cursor_position(
+    window_query: &Query<&Window, With<PrimaryWindow>>,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let window = window_query.get_single().ok()?;
+    let (camera, camera_transform) = camera_query.get_single().ok()?;
+    camera.viewport_to_world_2d(camera_transform, window.cursor_position()?).ok()
+}
+
+fn switch_level(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut editor_level: ResMut<EditorLevel>,
+    config: Res<GameConfig>,
+) {
+    let index = editor_level.index;
+    let index = if keyboard_input.just_pressed(PREVIOUS_LEVEL_KEY) {
+        index.checked_sub(1)
+    } else if keyboard_input.just_pressed(NEXT_LEVEL_KEY) {
+        Some(index + 1).filter(|index| *index < config.levels.files.len())
+    } else {
+        return;
+    };
+    if let Some(index) = index {
+        let selected = editor_level.selected;
+        *editor_level = EditorLevel { selected, ..EditorLevel::open(&config, index) };
+    }
+}
+
+// Only writes to the level when something changes, so it's only redrawn then
+fn edit_level(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    interaction_query: Query<&Interaction>,
+    mut editor_level: ResMut<EditorLevel>,
+    config: Res<GameConfig>,
+) {
+    let palette = palette(&config);
+    if keyboard_input.just_pressed(BRICK_TYPE_KEY) {
+        editor_level.selected = (editor_level.selected + 1) % palette.len();
+    }
+    let Some(cursor) = cursor_position(&window_query, &camera_query) else {
+        return;
+    };
+    if keyboard_input.just_pressed(PADDLE_KEY) {
+        editor_level.paddle_x = Some(cursor.x);
+    }
+    if keyboard_input.just_pressed(BALL_KEY) {
+        editor_level.ball = Some(cursor);
+    }
+
+    // Clicks on buttons, like the sandbox's, are for the buttons
+    let over_ui = interaction_query.iter().any(|interaction| *interaction != Interaction::None);
+    if over_ui {
+        return;
+    }
+    let brush = if mouse_input.pressed(ERASE_BUTTON) {
+        None
+    } else if mouse_input.pressed(PAINT_BUTTON) {
+        // The selection can point past the palette after a reload drops brick types
+        Some(palette.get(editor_level.selected).unwrap_or(&PLAIN_BRICK).to_string())
+    } else {
+        return;
+    };
+    let Some((row, column)) = cell_at(&config, editor_level.columns, cursor) else {
+        return;
+    };
+    // The grid can outgrow the level if a reload makes room for more bricks
+    let Some(cell) = editor_level.cells.get(row).and_then(|cells| cells.get(column)) else {
+        return;
+    };
+    if *cell != brush {
+        editor_level.cells[row][column] = brush;
+    }
+}
+
+// Saving and play-testing both go through the level format, so what's played is what would load
+fn use_level(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut editor_level: ResMut<EditorLevel>,
+    mut play_test: ResMut<PlayTest>,
+    mut next_state: ResMut<NextState<GameState>>,
+    config: Res<GameConfig>,
+) {
+    if keyboard_input.just_pressed(MENU_KEY) {
+        next_state.set(GameState::MainMenu);
+        return;
+    }
+    let save = keyboard_input.just_pressed(SAVE_KEY);
+    let play = keyboard_input.just_pressed(PLAY_TEST_KEY);
+    if !save && !play {
+        return;
+    }
+
+    let text = editor_level.to_level().to_string();
+    let level = Level::parse(&text).and_then(|level| {
+        level.validate(&config).map_err(LevelError::Invalid)?;
+        Ok(level)
+    });
+    let level = match level {
+        Ok(level) => level,
+        Err(error) => {
+            editor_level.status = format!("Can't use this level, {error}");
+            return;
+        }
+    };
+    if save {
+        let full_path = asset_path(&editor_level.path);
+        let saved = full_path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&full_path, &text));
+        editor_level.status = match saved {
+            Ok(()) => format!("Saved {}", editor_level.path.display()),
+            Err(error) => format!("Couldn't save {}: {error}", full_path.display()),
+        };
+    }
+    if play {
+        play_test.0 = Some(level);
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn update_editor_hud(
+    editor_level: Res<EditorLevel>,
+    mut hud_query: Query<(&mut Text, Ref<EditorHud>)>,
+    config: Res<GameConfig>,
+) {
+    let palette = palette(&config);
+    let brick_type = palette.get(editor_level.selected).unwrap_or(&PLAIN_BRICK);
+    for (mut text, hud) in &mut hud_query {
+        // The HUD is spawned again after every play-test
+        if !editor_level.is_changed() && !hud.is_added() {
+            continue;
+        }
+        text.0 = format!(
+            "{} ({})  Painting: {brick_type}\n{}\n\
+             Left click paint, right click erase, Tab brick type, P paddle start, B ball start\n\
+             PageUp/PageDown other levels, S save, Enter play-test, Backspace menu",
+            editor_level.name,
+            editor_level.path.display(),
+            editor_level.status,
+        );
+    }
+}
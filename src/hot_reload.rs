@@ -0,0 +1,336 @@
+//! Re-applies `GameConfig` to the running game whenever its file changes.
+//!
+//! Changes are handled in one of four ways:
+//! - Values that live entities can take in place are applied to them: the background, sizes and
+//!   colors, brick points, collision layers, paddle speed and position, ball damage, and ball speed,
+//!   which rescales each `Velocity` but keeps its direction. Bricks keep the damage they've taken,
+//!   and balls slowed by slow ball stay slowed.
+//! - Changed component sets are diffed per archetype. Components that were dropped are stashed,
+//!   so undoing the edit brings their old values back, and new ones are restored or defaulted.
+//! - Starting lives only apply to the next game, lives already lost aren't given back.
+//! - Structural changes respawn what they affect. A new arena respawns the walls and the brick
+//!   field, a new brick size or spacing or a different file for the current level respawns the
+//!   brick field, and new ball spawns respawn the balls. Respawning the brick field brings back
+//!   bricks that were already destroyed. Edits to a level file itself are picked up the next time
+//!   its field is spawned.
+//!
+//! Only a running game is updated, the menu and the editor just pick up the new config.
+//! A file that fails to load is reported and the game keeps the config it had.
+use std::{path::PathBuf, time::SystemTime};
+
+use bevy::prelude::*;
+
+use crate::{
+    bricks::BrickType,
+    collision::CollisionLayers,
+    config::{color, GameConfig},
+    levels::{load_level, CurrentLevel, LevelStart},
+    powerups::Slowed,
+    scoring::Points,
+    spawn_balls, spawn_bricks, spawn_walls,
+    stash::StashCommandsExt,
+    states::InGame,
+    Destructable, Destructor, EntityType, ScoreboardUi, Velocity,
+};
+
+/// How often the config file's modification time is checked.
+const POLL_INTERVAL_SECS: f32 = 0.5;
+
+#[derive(Resource)]
+struct ConfigWatcher {
+    path: PathBuf,
+    last_modified: Option<SystemTime>,
+    timer: Timer,
+}
+
+/// Sent after `GameConfig` has been replaced by a newer version of its file.
+#[derive(Event)]
+pub struct ConfigReloaded {
+    pub previous: GameConfig,
+}
+
+/// Watches the config file at `path`, the full path rather than one relative to the assets directory.
+pub(crate) fn watch(app: &mut App, path: PathBuf) {
+    let last_modified = modified(&path);
+    app.insert_resource(ConfigWatcher {
+        path,
+        last_modified,
+        timer: Timer::from_seconds(POLL_INTERVAL_SECS, TimerMode::Repeating),
+    })
+    .add_event::<ConfigReloaded>()
+    .add_systems(
+        Update,
+        (
+            reload_config,
+            // Outside a game there's nothing to update, the next game is spawned from the new config
+            (respawn_changed_structure, apply_changed_values, apply_scoreboard_values)
+                .chain()
+                .run_if(in_state(InGame)),
+        )
+            .chain(),
+    );
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn reload_config(
+    mut watcher: ResMut<ConfigWatcher>,
+    mut config: ResMut<GameConfig>,
+    mut reloaded: EventWriter<ConfigReloaded>,
+    // Real time, so the file is still watched while the game is paused
+    time: Res<Time<Real>>,
+) {
+    if !watcher.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let last_modified = modified(&watcher.path);
+    if last_modified.is_none() || last_modified == watcher.last_modified {
+        return;
+    }
+    watcher.last_modified = last_modified;
+
+    match GameConfig::load(&watcher.path) {
+        Ok(new_config) if new_config == *config => {}
+        Ok(new_config) => {
+            info!("Reloaded {}", watcher.path.display());
+            let previous = std::mem::replace(&mut *config, new_config);
+            reloaded.send(ConfigReloaded { previous });
+        }
+        Err(error) => {
+            error!("{}: {error}\nKeeping the current config.", watcher.path.display());
+        }
+    }
+}
+
+fn respawn_changed_structure(
+    mut commands: Commands,
+    mut reloaded: EventReader<ConfigReloaded>,
+    config: Res<GameConfig>,
+    current_level: Res<CurrentLevel>,
+    entity_query: Query<(Entity, &EntityType)>,
+) {
+    for ConfigReloaded { previous } in reloaded.read() {
+        let arena_changed = previous.arena.left_wall != config.arena.left_wall
+            || previous.arena.right_wall != config.arena.right_wall
+            || previous.arena.bottom_wall != config.arena.bottom_wall
+            || previous.arena.top_wall != config.arena.top_wall
+            || previous.arena.wall_thickness != config.arena.wall_thickness
+            || previous.arena.bottom_is_loss_zone != config.arena.bottom_is_loss_zone;
+        // The paddle moving also moves the brick field, which sits a fixed gap above it
+        let bricks_changed = arena_changed
+            || previous.brick_positions() != config.brick_positions()
+            || previous.levels.files.get(**current_level) != config.levels.files.get(**current_level)
+            || previous.brick.size != config.brick.size;
+        let balls_changed = previous.ball.spawns != config.ball.spawns;
+
+        let mut respawn = |entity_type: EntityType, spawn: &dyn Fn(&mut Commands, &GameConfig)| {
+            for (entity, _) in entity_query.iter().filter(|(_, other)| **other == entity_type) {
+                commands.entity(entity).despawn_recursive();
+            }
+            spawn(&mut commands, &config);
+        };
+        if arena_changed {
+            respawn(EntityType::Wall, &spawn_walls);
+        }
+        if bricks_changed {
+            let level = load_level(&config, **current_level);
+            // Only the next serve uses the level's new start positions, what's in play stays put
+            respawn(EntityType::Brick, &|commands, config| {
+                spawn_bricks(commands, config, level.as_ref());
+                commands.insert_resource(LevelStart::of(level.as_ref()));
+            });
+        }
+        if balls_changed {
+            respawn(EntityType::Ball, &spawn_balls);
+        }
+
+        // Entities that weren't respawned need their component sets brought up to date
+        for entity_type in EntityType::ALL {
+            let respawned = match entity_type {
+                EntityType::Wall => arena_changed,
+                EntityType::Brick => bricks_changed,
+                EntityType::Ball => balls_changed,
+                EntityType::Paddle | EntityType::Capsule | EntityType::Projectile | EntityType::Score => false,
+            };
+            let old_components = previous.components(entity_type);
+            let new_components = config.components(entity_type);
+            if respawned || old_components == new_components {
+                continue;
+            }
+            for (entity, _) in entity_query.iter().filter(|(_, other)| **other == entity_type) {
+                let mut entity = commands.entity(entity);
+                for component in old_components.iter().filter(|c| !new_components.contains(c)) {
+                    entity.stash_component(*component);
+                }
+                for component in new_components.iter().filter(|c| !old_components.contains(c)) {
+                    entity.restore_or_default_component(*component);
+                }
+            }
+        }
+    }
+}
+
+fn apply_changed_values(
+    mut reloaded: EventReader<ConfigReloaded>,
+    config: Res<GameConfig>,
+    mut clear_color: ResMut<ClearColor>,
+    mut entity_query: Query<(
+        &EntityType,
+        &mut Transform,
+        Option<&mut Sprite>,
+        Option<&MeshMaterial2d<ColorMaterial>>,
+        Option<&mut Velocity>,
+        Option<&mut Points>,
+        Option<&mut CollisionLayers>,
+        Option<&Destructable>,
+        Option<&mut Destructor>,
+        Option<&BrickType>,
+        Option<&mut Slowed>,
+    )>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for ConfigReloaded { previous } in reloaded.read() {
+        clear_color.0 = color(config.arena.background_color);
+
+        for (entity_type, mut transform, sprite, material, velocity, points, layers, destructable, destructor, brick_type, slowed) in
+            &mut entity_query
+        {
+            if let (Some(mut layers), Some(new_layers)) = (layers, config.collision_layers(*entity_type)) {
+                *layers = new_layers;
+            }
+            match entity_type {
+                EntityType::Paddle => {
+                    transform.scale = Vec2::from(config.paddle.size).extend(1.0);
+                    transform.translation.y = config.paddle_y();
+                    if let Some(mut sprite) = sprite {
+                        sprite.color = color(config.paddle.color);
+                    }
+                }
+                EntityType::Ball => {
+                    transform.scale = Vec2::splat(config.ball.diameter).extend(1.0);
+                    // Balls that were given their own color when spawned keep it
+                    let material = material.and_then(|material| materials.get_mut(material));
+                    if let Some(material) = material.filter(|material| material.color == color(previous.ball.color)) {
+                        material.color = color(config.ball.color);
+                    }
+                    let speed_changed = previous.ball.speed != config.ball.speed
+                        || (slowed.is_some() && previous.power_ups.slow_ball_scale != config.power_ups.slow_ball_scale);
+                    if let Some(mut velocity) = velocity.filter(|_| speed_changed) {
+                        // Slowed balls stay slowed, and speed up to the new speed once slow ball runs out
+                        let speed = match slowed {
+                            Some(mut slowed) => {
+                                slowed.0 = config.ball.speed;
+                                config.ball.speed * config.power_ups.slow_ball_scale
+                            }
+                            None => config.ball.speed,
+                        };
+                        velocity.0 = velocity.normalize_or_zero() * speed;
+                    }
+                    if let Some(mut destructor) = destructor {
+                        destructor.damage = config.ball.damage;
+                    }
+                }
+                EntityType::Wall => {
+                    if let Some(mut sprite) = sprite {
+                        sprite.color = color(config.wall.color);
+                    }
+                }
+                EntityType::Brick => {
+                    let brick_type = brick_type.map(|brick_type| brick_type.0.as_str());
+                    if let Some(mut sprite) = sprite {
+                        // Damaged bricks keep showing their damage
+                        let hit_points = destructable.map_or(config.brick.hit_points, |destructable| destructable.hit_points);
+                        sprite.color = config.brick_color(brick_type, hit_points);
+                    }
+                    if let Some(mut points) = points {
+                        let type_config = brick_type.and_then(|name| config.brick.types.get(name));
+                        points.0 = type_config.and_then(|t| t.points).unwrap_or(config.brick.points);
+                    }
+                }
+                EntityType::Capsule => {
+                    transform.scale = Vec2::from(config.power_ups.capsule_size).extend(1.0);
+                    if let Some(mut velocity) = velocity {
+                        velocity.0 = Vec2::new(0.0, -config.power_ups.fall_speed);
+                    }
+                }
+                EntityType::Projectile => {
+                    transform.scale = Vec2::from(config.lasers.size).extend(1.0);
+                    if let Some(mut sprite) = sprite {
+                        sprite.color = color(config.lasers.color);
+                    }
+                }
+                EntityType::Score => {}
+            }
+        }
+    }
+}
+
+fn apply_scoreboard_values(
+    mut reloaded: EventReader<ConfigReloaded>,
+    config: Res<GameConfig>,
+    mut scoreboard_query: Query<(Entity, &mut Node), With<ScoreboardUi>>,
+    mut writer: TextUiWriter,
+) {
+    // Only the newest config matters, however many reloads there were
+    if reloaded.is_empty() {
+        return;
+    }
+    reloaded.clear();
+    let scoreboard = &config.scoreboard;
+    for (scoreboard_entity, mut node) in &mut scoreboard_query {
+        node.top = Val::Px(scoreboard.padding);
+        node.left = Val::Px(scoreboard.padding);
+        // Labels and values alternate, starting with "Score: "
+        for index in 0..4 {
+            writer.font(scoreboard_entity, index).font_size = scoreboard.font_size;
+            let text_color = if index % 2 == 0 { scoreboard.text_color } else { scoreboard.score_color };
+            *writer.color(scoreboard_entity, index) = TextColor(color(text_color));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::powerups::{apply_power_ups, expire_power_ups, ActivePowerUps, PowerUp};
+
+    #[test]
+    fn slowed_balls_speed_up_to_the_reloaded_speed() {
+        let mut world = World::new();
+        let config = GameConfig::default();
+        let (speed, scale, duration) = (config.ball.speed, config.power_ups.slow_ball_scale, config.power_ups.duration);
+        world.insert_resource(config);
+        world.init_resource::<ClearColor>();
+        world.init_resource::<Assets<ColorMaterial>>();
+        world.init_resource::<Time>();
+        world.init_resource::<ActivePowerUps>();
+        world.init_resource::<Events<ConfigReloaded>>();
+        let direction = Vec2::new(0.6, 0.8);
+        let ball = world.spawn((EntityType::Ball, Transform::default(), Velocity(direction * speed))).id();
+        let ball_speed = |world: &World| world.get::<Velocity>(ball).unwrap().length();
+
+        world.resource_mut::<ActivePowerUps>().activate(PowerUp::SlowBall, duration);
+        world.run_system_once(apply_power_ups).unwrap();
+        assert!((ball_speed(&world) - speed * scale).abs() < 1e-3);
+
+        let mut faster = world.resource::<GameConfig>().clone();
+        faster.ball.speed = speed * 2.0;
+        let previous = std::mem::replace(&mut *world.resource_mut::<GameConfig>(), faster);
+        world.send_event(ConfigReloaded { previous });
+        world.run_system_once(apply_changed_values).unwrap();
+        assert!((ball_speed(&world) - speed * 2.0 * scale).abs() < 1e-3);
+
+        world.resource_mut::<Time>().advance_by(Duration::from_secs_f32(duration));
+        world.run_system_once(expire_power_ups).unwrap();
+        world.run_system_once(apply_power_ups).unwrap();
+        let velocity = world.get::<Velocity>(ball).unwrap().0;
+        assert!(velocity.abs_diff_eq(direction * speed * 2.0, 1e-3), "{velocity}");
+        assert!(!world.entity(ball).contains::<Slowed>());
+    }
+}
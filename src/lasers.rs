@@ -0,0 +1,205 @@
+//! Laser shots, fired by paddles while the laser power-up is active.
+//!
+//! Holding `FIRE_KEY` makes every paddle, that is every `Controllable` that doesn't move on its own,
+//! fire a `Projectile` straight up. Shots are `Destructor`s with a `Velocity` and a `Collider`, so
+//! they go through the same collision pipeline as balls and destroy bricks the same way. A shot is
+//! gone as soon as it touches anything it collides with, or once its lifetime runs out. By default
+//! that's walls and bricks only: shots pass through balls and capsules, since a shot hitting a ball
+//! would bounce it and trade momentum with it like any other moving body. Volleys are at least
+//! `LaserConfig::fire_interval` apart, and paddles hold fire while `LaserConfig::max_projectiles`
+//! shots are in flight.
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    config::{color, GameConfig},
+    insert_components,
+    powerups::{ActivePowerUps, PowerUp, StuckTo},
+    states::InGame,
+    CollisionEvent, Controllable, EntityType, Velocity,
+};
+
+pub const FIRE_KEY: KeyCode = KeyCode::ArrowUp;
+
+// Shots fly behind the paddle they come from
+const PROJECTILE_Z: f32 = -0.5;
+
+/// A laser shot. Despawned once `lifetime` runs out, if it hasn't hit anything first.
+#[derive(Component, Debug)]
+pub struct Projectile {
+    pub lifetime: Timer,
+}
+
+/// Counts down until paddles can fire again.
+#[derive(Resource, Debug, Default)]
+pub struct LaserCooldown(pub Timer);
+
+/// Spawns a shot heading up from `position` at `LaserConfig::speed`.
+pub fn spawn_projectile(commands: &mut Commands, config: &GameConfig, position: Vec2) -> Entity {
+    let lasers = &config.lasers;
+    let mut projectile_entity = commands.spawn((
+        Sprite {
+            color: color(lasers.color),
+            ..default()
+        },
+        Transform {
+            translation: position.extend(PROJECTILE_Z),
+            scale: Vec2::from(lasers.size).extend(1.0),
+            ..default()
+        },
+        EntityType::Projectile,
+        Projectile {
+            lifetime: Timer::from_seconds(lasers.lifetime, TimerMode::Once),
+        },
+        config.collision_layers(EntityType::Projectile).unwrap_or_default(),
+        StateScoped(InGame),
+    ));
+    insert_components(&mut projectile_entity, &lasers.components, Vec2::new(0.0, lasers.speed));
+    projectile_entity.id()
+}
+
+pub(crate) fn fire_lasers(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    active: Res<ActivePowerUps>,
+    mut cooldown: ResMut<LaserCooldown>,
+    // Balls held by a sticky paddle have no `Velocity` either, but they aren't paddles
+    paddle_query: Query<&Transform, (With<Controllable>, Without<Velocity>, Without<StuckTo>)>,
+    projectile_query: Query<(), With<Projectile>>,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+) {
+    cooldown.0.tick(time.delta());
+    let ready = cooldown.0.finished() || cooldown.0.duration().is_zero();
+    if !ready || !active.is_active(PowerUp::Laser) || !keyboard_input.pressed(FIRE_KEY) {
+        return;
+    }
+
+    let room = config.lasers.max_projectiles.saturating_sub(projectile_query.iter().count());
+    for paddle in paddle_query.iter().take(room) {
+        // From just above the middle of the paddle's top edge
+        let position = paddle.translation.truncate() + Vec2::Y * (paddle.scale.y + config.lasers.size[1]) / 2.0;
+        spawn_projectile(&mut commands, &config, position);
+    }
+    cooldown.0 = Timer::from_seconds(config.lasers.fire_interval, TimerMode::Once);
+}
+
+// Shots are spent by hitting anything at all, or by running out of time
+pub(crate) fn despawn_spent_projectiles(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut projectile_query: Query<(Entity, &mut Projectile)>,
+    time: Res<Time>,
+) {
+    // A shot can hit something in the same tick it runs out, it's only despawned once
+    let mut spent = HashSet::new();
+    for event in collision_events.read().filter(|event| event.started()) {
+        for entity in [event.e1, event.e2] {
+            if projectile_query.contains(entity) {
+                spent.insert(entity);
+            }
+        }
+    }
+    for (entity, mut projectile) in &mut projectile_query {
+        if projectile.lifetime.tick(time.delta()).finished() {
+            spent.insert(entity);
+        }
+    }
+    for entity in spent {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{Collision, CollisionPhase, Manifold};
+
+    fn world() -> World {
+        let mut world = World::new();
+        let config = GameConfig::default();
+        let mut active = ActivePowerUps::default();
+        active.activate(PowerUp::Laser, config.power_ups.duration);
+        world.insert_resource(config);
+        world.insert_resource(active);
+        world.init_resource::<LaserCooldown>();
+        world.init_resource::<Time>();
+        world.init_resource::<ButtonInput<KeyCode>>();
+        world.init_resource::<Events<CollisionEvent>>();
+        world.resource_mut::<ButtonInput<KeyCode>>().press(FIRE_KEY);
+        world
+    }
+
+    fn fire(world: &mut World, secs: f32) -> usize {
+        world.resource_mut::<Time>().advance_by(Duration::from_secs_f32(secs));
+        world.run_system_once(fire_lasers).unwrap();
+        world.query::<&Projectile>().iter(world).count()
+    }
+
+    #[test]
+    fn paddles_fire_while_the_power_up_is_active() {
+        let mut world = world();
+        world.spawn((Controllable, Transform::default()));
+        world.spawn((Controllable, Transform::default()));
+        // Neither a moving ball nor one held by a sticky paddle fires
+        world.spawn((Controllable, Transform::default(), Velocity(Vec2::Y)));
+        let paddle = world.spawn_empty().id();
+        world.spawn((Controllable, Transform::default(), StuckTo { paddle, offset: Vec2::ZERO }));
+        assert_eq!(fire(&mut world, 0.0), 2);
+    }
+
+    #[test]
+    fn nothing_fires_without_the_power_up() {
+        let mut world = world();
+        world.spawn((Controllable, Transform::default()));
+        world.resource_mut::<ActivePowerUps>().clear();
+        assert_eq!(fire(&mut world, 0.0), 0);
+    }
+
+    #[test]
+    fn volleys_are_spaced_and_capped() {
+        let mut world = world();
+        let lasers = world.resource::<GameConfig>().lasers.clone();
+        world.spawn((Controllable, Transform::default()));
+
+        assert_eq!(fire(&mut world, 0.0), 1);
+        assert_eq!(fire(&mut world, lasers.fire_interval / 2.0), 1);
+        assert_eq!(fire(&mut world, lasers.fire_interval / 2.0), 2);
+        for _ in 0..lasers.max_projectiles * 2 {
+            fire(&mut world, lasers.fire_interval);
+        }
+        assert_eq!(world.query::<&Projectile>().iter(&world).count(), lasers.max_projectiles);
+    }
+
+    #[test]
+    fn shots_are_spent_on_a_hit_or_with_time() {
+        let mut world = world();
+        let lifetime = world.resource::<GameConfig>().lasers.lifetime;
+        let shot = |world: &mut World| {
+            let lifetime = Timer::from_seconds(lifetime, TimerMode::Once);
+            world.spawn(Projectile { lifetime }).id()
+        };
+        let hit = shot(&mut world);
+        let missed = shot(&mut world);
+        let brick = world.spawn_empty().id();
+
+        world.send_event(CollisionEvent {
+            e1: brick,
+            e2: hit,
+            hit_side_of_e1: Collision::Bottom,
+            manifold: Manifold { normal: Vec2::NEG_Y, depth: 1.0, point: Vec2::ZERO },
+            phase: CollisionPhase::Started,
+        });
+        world.run_system_once(despawn_spent_projectiles).unwrap();
+        assert!(world.get_entity(hit).is_err());
+        assert!(world.get_entity(missed).is_ok());
+        assert!(world.get_entity(brick).is_ok());
+
+        world.resource_mut::<Time>().advance_by(Duration::from_secs_f32(lifetime));
+        world.run_system_once(despawn_spent_projectiles).unwrap();
+        assert!(world.get_entity(missed).is_err());
+    }
+}
@@ -0,0 +1,552 @@
+//! Hand-authored brick layouts, loaded from level files in the assets directory.
+//!
+//! A level file has three sections. Lines starting with `#` are comments, except in the grid.
+//! ```text
+//! [header]
+//! name: Opening
+//! author: Someone
+//! paddle: -100
+//! ball: -100, -150
+//!
+//! [legend]
+//! B: brick
+//! X: indestructible
+//!
+//! [grid]
+//! BBBBBBBB
+//! X.B..B.X
+//! ```
+//! The header holds the level's metadata, and optionally the x coordinate the paddle starts at and
+//! the position balls are served from, in place of the config's `ball.spawns`. The legend maps
+//! each character the grid uses to a brick type from `BrickConfig::types`, or to `brick` for a
+//! plain brick. `.` and spaces are empty cells. A level needs at least one destructible brick,
+//! since it's cleared once none are left.
+//! Every line after `[grid]` is a row of cells, from the top down, so blank lines are empty rows.
+//!
+//! Cells sit on the same lattice as the computed brick grid: a level as wide and as tall as that
+//! grid covers it exactly, narrower levels are centered and shorter ones hang from the top row.
+//!
+//! `LevelsConfig::files` are played in order. Clearing a level clears the field and lays out the
+//! next one behind the `LevelComplete` screen, and after `LevelsConfig::transition` seconds, or on
+//! Space, a ball is served and play resumes. Score and lives carry over. Clearing the last level
+//! wins the game.
+//!
+//! The editor, see `editor`, writes levels back out through `Level`'s `Display`.
+use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+
+use bevy::prelude::*;
+
+use crate::{
+    collision::CollisionTracker,
+    config::{asset_path, GameConfig},
+    lives::serve_ball,
+    powerups::ActivePowerUps,
+    spawn_bricks,
+    stash::StashableComponent,
+    states::{spawn_screen, GameState},
+    EntityType,
+};
+
+/// The legend entry for a brick without a type.
+pub const PLAIN_BRICK: &str = "brick";
+
+/// A parsed level file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    pub name: String,
+    pub author: Option<String>,
+    /// Where the paddle starts, instead of the middle of the arena.
+    pub paddle_x: Option<f32>,
+    /// Where balls are served from, at the start of the level and after losing the last one.
+    pub ball: Option<Vec2>,
+    pub legend: BTreeMap<char, String>,
+    /// Rows from the top down. Rows can be shorter than `width`.
+    pub rows: Vec<Vec<char>>,
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),
+    /// The first line that couldn't be read, counting from 1.
+    Parse { line: usize, message: String },
+    /// Everything `Level::validate` found wrong with the level for the current config.
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for Level {
+    // The same layout `parse` reads, with every section written out
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[header]")?;
+        writeln!(f, "name: {}", self.name)?;
+        if let Some(author) = &self.author {
+            writeln!(f, "author: {author}")?;
+        }
+        if let Some(paddle_x) = self.paddle_x {
+            writeln!(f, "paddle: {paddle_x}")?;
+        }
+        if let Some(ball) = self.ball {
+            writeln!(f, "ball: {}, {}", ball.x, ball.y)?;
+        }
+        writeln!(f, "\n[legend]")?;
+        for (cell, brick_type) in &self.legend {
+            writeln!(f, "{cell}: {brick_type}")?;
+        }
+        writeln!(f, "\n[grid]")?;
+        for row in &self.rows {
+            writeln!(f, "{}", row.iter().collect::<String>())?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io(error) => write!(f, "couldn't read the file: {error}"),
+            LevelError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            LevelError::Invalid(problems) => {
+                write!(f, "invalid level:")?;
+                for problem in problems {
+                    write!(f, "\n  - {problem}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    None,
+    Header,
+    Legend,
+    Grid,
+}
+
+fn is_empty_cell(cell: char) -> bool {
+    cell == '.' || cell == ' '
+}
+
+fn parse_point(value: &str) -> Option<Vec2> {
+    let (x, y) = value.split_once(',')?;
+    Some(Vec2::new(x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+impl Level {
+    pub fn load(path: &Path) -> Result<Level, LevelError> {
+        let text = fs::read_to_string(path).map_err(LevelError::Io)?;
+        Level::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Level, LevelError> {
+        let mut section = Section::None;
+        let mut name = None;
+        let mut author = None;
+        let mut paddle_x = None;
+        let mut ball = None;
+        let mut legend = BTreeMap::new();
+        let mut rows = vec![];
+        for (index, line) in text.lines().enumerate() {
+            let fail = |message: String| LevelError::Parse { line: index + 1, message };
+            if section == Section::Grid {
+                rows.push(line.trim_end().chars().collect::<Vec<_>>());
+                continue;
+            }
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line {
+                "[header]" => section = Section::Header,
+                "[legend]" => section = Section::Legend,
+                "[grid]" => section = Section::Grid,
+                _ => {
+                    let Some((key, value)) = line.split_once(':') else {
+                        return Err(fail(format!("expected `key: value` or a section, found `{line}`")));
+                    };
+                    let (key, value) = (key.trim(), value.trim());
+                    match section {
+                        Section::Header => match key {
+                            "name" => name = Some(value.to_string()),
+                            "author" => author = Some(value.to_string()),
+                            "paddle" => {
+                                let x = value.parse().map_err(|_| fail(format!("expected an x coordinate, found `{value}`")))?;
+                                paddle_x = Some(x);
+                            }
+                            "ball" => {
+                                let Some(position) = parse_point(value) else {
+                                    return Err(fail(format!("expected `x, y`, found `{value}`")));
+                                };
+                                ball = Some(position);
+                            }
+                            _ => return Err(fail(format!("unknown header field `{key}`"))),
+                        },
+                        Section::Legend => {
+                            let mut chars = key.chars();
+                            let (Some(cell), None) = (chars.next(), chars.next()) else {
+                                return Err(fail(format!("legend keys are single characters, found `{key}`")));
+                            };
+                            if is_empty_cell(cell) {
+                                return Err(fail(format!("`{cell}` is always an empty cell")));
+                            }
+                            if legend.insert(cell, value.to_string()).is_some() {
+                                return Err(fail(format!("`{cell}` is in the legend twice")));
+                            }
+                        }
+                        Section::None | Section::Grid => {
+                            return Err(fail("expected [header], [legend] or [grid] first".to_string()));
+                        }
+                    }
+                }
+            }
+        }
+        while rows.last().is_some_and(|row| row.is_empty()) {
+            rows.pop();
+        }
+
+        let mut problems = vec![];
+        let Some(name) = name else {
+            return Err(LevelError::Invalid(vec!["the header needs a name".to_string()]));
+        };
+        if rows.iter().flatten().all(|cell| is_empty_cell(*cell)) {
+            problems.push("the grid has no bricks".to_string());
+        }
+        for (row, cells) in rows.iter().enumerate() {
+            for (column, cell) in cells.iter().enumerate() {
+                if !is_empty_cell(*cell) && !legend.contains_key(cell) {
+                    problems.push(format!("`{cell}` in row {}, column {} isn't in the legend", row + 1, column + 1));
+                }
+            }
+        }
+        if !problems.is_empty() {
+            return Err(LevelError::Invalid(problems));
+        }
+        Ok(Level {
+            name,
+            author,
+            paddle_x,
+            ball,
+            legend,
+            rows,
+        })
+    }
+
+    /// The number of columns, that is the length of the longest row.
+    pub fn width(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Checks that the level and its start positions fit the arena, and that it only uses brick
+    /// types the config has.
+    pub fn validate(&self, config: &GameConfig) -> Result<(), Vec<String>> {
+        let mut problems = vec![];
+        let (n_rows, n_columns) = config.brick_grid_size();
+        if self.width() > n_columns || self.height() > n_rows {
+            problems.push(format!(
+                "the grid is {} by {} cells, but only {n_columns} by {n_rows} fit in the arena",
+                self.width(),
+                self.height()
+            ));
+        }
+        if let Some(paddle_x) = self.paddle_x {
+            if !config.contains(Vec2::new(paddle_x, config.paddle_y())) {
+                problems.push(format!("the paddle ({paddle_x}) must start inside the arena"));
+            }
+        }
+        if let Some(ball) = self.ball {
+            if !config.contains(ball) {
+                problems.push(format!("the ball ({ball}) must start inside the arena"));
+            }
+        }
+        for (cell, brick_type) in &self.legend {
+            if brick_type != PLAIN_BRICK && !config.brick.types.contains_key(brick_type) {
+                problems.push(format!("`{cell}` is a `{brick_type}`, which isn't one of brick.types"));
+            }
+        }
+        // A level is complete once no `Destructable` is left, so one without any would end right away
+        let brick = &config.brick;
+        let destructable = self.brick_positions(config).into_iter().any(|(_, brick_type)| {
+            let type_config = brick_type.and_then(|name| brick.types.get(name));
+            let components = type_config.and_then(|t| t.components.as_deref()).unwrap_or(&brick.components);
+            components.contains(&StashableComponent::Destructable)
+        });
+        if !destructable {
+            problems.push("there must be at least one destructible brick".to_string());
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// The center of every brick and its type, `None` for plain bricks, row by row from the top.
+    pub fn brick_positions(&self, config: &GameConfig) -> Vec<(Vec2, Option<&str>)> {
+        let width = self.width();
+        let mut positions = vec![];
+        for (row, cells) in self.rows.iter().enumerate() {
+            for (column, cell) in cells.iter().enumerate() {
+                let Some(brick_type) = self.legend.get(cell) else {
+                    continue;
+                };
+                let position = cell_center(config, width, row, column);
+                positions.push((position, (brick_type != PLAIN_BRICK).then_some(brick_type.as_str())));
+            }
+        }
+        positions
+    }
+}
+
+// The distance between the centers of neighbouring cells
+fn cell_step(config: &GameConfig) -> Vec2 {
+    Vec2::from(config.brick.size) + config.brick.gap_between_bricks
+}
+
+// The top left cell of a level `width` cells wide,
+// with the same top row and horizontal center as `GameConfig::brick_positions`
+fn first_cell_center(config: &GameConfig, width: usize) -> Vec2 {
+    let brick = &config.brick;
+    let brick_size = Vec2::from(brick.size);
+    let (n_rows, _) = config.brick_grid_size();
+    let top_row_y =
+        config.paddle_y() + brick.gap_to_paddle + brick_size.y / 2.0 + n_rows.saturating_sub(1) as f32 * cell_step(config).y;
+    let center_of_bricks = (config.arena.left_wall + config.arena.right_wall) / 2.0;
+    let width = width as f32;
+    let left_edge_of_bricks =
+        center_of_bricks - width / 2.0 * brick_size.x - (width - 1.0).max(0.0) / 2.0 * brick.gap_between_bricks;
+    Vec2::new(left_edge_of_bricks + brick_size.x / 2.0, top_row_y)
+}
+
+/// The center of the cell at `row` and `column` of a level `width` cells wide.
+pub fn cell_center(config: &GameConfig, width: usize, row: usize, column: usize) -> Vec2 {
+    let step = cell_step(config);
+    first_cell_center(config, width) + Vec2::new(column as f32 * step.x, -(row as f32) * step.y)
+}
+
+/// The `(row, column)` of the cell under `position`, the gap around it included, in a level
+/// `width` cells wide and as tall as the computed brick grid.
+pub fn cell_at(config: &GameConfig, width: usize, position: Vec2) -> Option<(usize, usize)> {
+    let (n_rows, _) = config.brick_grid_size();
+    let step = cell_step(config);
+    let offset = position - first_cell_center(config, width);
+    let column = (offset.x / step.x).round();
+    let row = (-offset.y / step.y).round();
+    let inside = column >= 0.0 && row >= 0.0 && (column as usize) < width && (row as usize) < n_rows;
+    inside.then_some((row as usize, column as usize))
+}
+
+/// Which of `LevelsConfig::files` is being played, counting from 0.
+#[derive(Resource, Debug, Default, Deref, DerefMut)]
+pub struct CurrentLevel(pub usize);
+
+/// Counts down the `LevelComplete` screen.
+#[derive(Resource, Debug, Default)]
+pub struct LevelTransition(pub Timer);
+
+/// The start positions of the level being played, see `Level::paddle_x` and `Level::ball`.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct LevelStart {
+    pub paddle_x: Option<f32>,
+    pub ball: Option<Vec2>,
+}
+
+impl LevelStart {
+    /// The start positions of `level`, or none when the computed brick grid is played instead.
+    pub fn of(level: Option<&Level>) -> Self {
+        LevelStart {
+            paddle_x: level.and_then(|level| level.paddle_x),
+            ball: level.and_then(|level| level.ball),
+        }
+    }
+}
+
+/// Whether the level at `index` is the last one the game has.
+pub fn is_last_level(config: &GameConfig, index: usize) -> bool {
+    index + 1 >= config.levels.files.len()
+}
+
+/// Loads the level file at `index` in `LevelsConfig::files`, if there is one that fits.
+/// Problems are logged, and give `None`.
+pub fn load_level(config: &GameConfig, index: usize) -> Option<Level> {
+    let path = config.levels.files.get(index)?;
+    let full_path = asset_path(path);
+    let level = Level::load(&full_path).and_then(|level| {
+        level.validate(config).map_err(LevelError::Invalid)?;
+        Ok(level)
+    });
+    match level {
+        Ok(level) => Some(level),
+        Err(error) => {
+            error!("{}: {error}\nUsing the computed brick grid instead.", full_path.display());
+            None
+        }
+    }
+}
+
+// Runs once on entering `LevelComplete`, so the next layout is already there behind the screen
+pub(crate) fn start_next_level(
+    mut commands: Commands,
+    mut current_level: ResMut<CurrentLevel>,
+    mut transition: ResMut<LevelTransition>,
+    mut level_start: ResMut<LevelStart>,
+    mut power_ups: ResMut<ActivePowerUps>,
+    mut collision_tracker: ResMut<CollisionTracker>,
+    mut entity_query: Query<(Entity, &EntityType, &mut Transform)>,
+    config: Res<GameConfig>,
+) {
+    // Leftover indestructible and regrowing bricks go too
+    for (entity, entity_type, _) in &entity_query {
+        if matches!(
+            entity_type,
+            EntityType::Ball | EntityType::Brick | EntityType::Capsule | EntityType::Projectile
+        ) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    collision_tracker.clear();
+    // The paddle and safety net go back to normal once play resumes
+    power_ups.clear();
+
+    **current_level += 1;
+    let level = load_level(&config, **current_level);
+    spawn_bricks(&mut commands, &config, level.as_ref());
+    *level_start = LevelStart::of(level.as_ref());
+    // Levels that don't say where the paddle starts start it in the middle, like the first one
+    let paddle_x = level_start.paddle_x.unwrap_or(0.0);
+    for (_, entity_type, mut transform) in &mut entity_query {
+        if *entity_type == EntityType::Paddle {
+            transform.translation.x = paddle_x;
+        }
+    }
+    transition.0 = Timer::from_seconds(config.levels.transition, TimerMode::Once);
+
+    let name = level.map_or_else(|| format!("level {}", **current_level + 1), |level| level.name);
+    spawn_screen(
+        &mut commands,
+        &config,
+        GameState::LevelComplete,
+        "Level Complete",
+        &format!("Up next: {name}"),
+    );
+}
+
+pub(crate) fn finish_transition(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut transition: ResMut<LevelTransition>,
+    level_start: Res<LevelStart>,
+    paddle_query: Query<(&Transform, &EntityType)>,
+    mut next_state: ResMut<NextState<GameState>>,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+) {
+    let finished = transition.0.tick(time.delta()).finished();
+    if !finished && !keyboard_input.just_pressed(KeyCode::Space) {
+        return;
+    }
+    next_state.set(GameState::Playing);
+    let paddle_x = paddle_query
+        .iter()
+        .find(|(_, entity_type)| **entity_type == EntityType::Paddle)
+        .map_or(0.0, |(transform, _)| transform.translation.x);
+    serve_ball(&mut commands, &config, level_start.ball, paddle_x);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(text: &str) -> (usize, String) {
+        match Level::parse(text) {
+            Err(LevelError::Parse { line, message }) => (line, message),
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn duplicate_legend_key_is_rejected() {
+        let (line, message) = parse_error("[header]\nname: Twice\n[legend]\nB: brick\nB: explosive\n[grid]\nB\n");
+        assert_eq!(line, 5);
+        assert!(message.contains("twice"), "{message}");
+    }
+
+    #[test]
+    fn unknown_header_field_is_rejected() {
+        let (line, message) = parse_error("[header]\nname: Speedy\nspeed: 2\n");
+        assert_eq!(line, 3);
+        assert!(message.contains("`speed`"), "{message}");
+    }
+
+    #[test]
+    fn fields_before_a_section_are_rejected() {
+        let (line, _) = parse_error("# A comment is fine\nname: Early\n[header]\n");
+        assert_eq!(line, 2);
+    }
+
+    #[test]
+    fn blank_grid_lines_are_empty_rows() {
+        let level = Level::parse("[header]\nname: Gaps\n[legend]\nB: brick\n[grid]\nBB\n\n  \nB\n\n\n").unwrap();
+        assert_eq!(level.height(), 4);
+        assert_eq!(level.width(), 2);
+        assert!(level.rows[1].is_empty() && level.rows[2].is_empty());
+    }
+
+    #[test]
+    fn written_levels_parse_back_the_same() {
+        let level = Level {
+            name: "Round trip".to_string(),
+            author: Some("Someone".to_string()),
+            paddle_x: Some(-100.5),
+            ball: Some(Vec2::new(20.0, -150.0)),
+            legend: BTreeMap::from([('B', PLAIN_BRICK.to_string()), ('X', "indestructible".to_string())]),
+            rows: vec!["BB.B".chars().collect(), vec![], "X..X".chars().collect()],
+        };
+        assert_eq!(Level::parse(&level.to_string()).unwrap(), level);
+
+        let shipped = Level::parse(include_str!("../assets/levels/03.level")).unwrap();
+        assert_eq!(Level::parse(&shipped.to_string()).unwrap(), shipped);
+    }
+
+    #[test]
+    fn level_without_destructible_bricks_is_rejected() {
+        let config = GameConfig::default();
+        let walls = Level::parse("[header]\nname: Walls\n[legend]\nX: indestructible\n[grid]\nX.X\n").unwrap();
+        let problems = walls.validate(&config).unwrap_err();
+        assert!(problems.iter().any(|problem| problem.contains("destructible")), "{problems:?}");
+
+        // A legend entry the grid doesn't use doesn't count
+        let unused = Level::parse("[header]\nname: Unused\n[legend]\nB: brick\nX: indestructible\n[grid]\nX\n").unwrap();
+        assert!(unused.validate(&config).is_err());
+
+        let mixed = Level::parse("[header]\nname: Mixed\n[legend]\nB: brick\nX: indestructible\n[grid]\nXBX\n").unwrap();
+        assert_eq!(mixed.validate(&config), Ok(()));
+    }
+
+    #[test]
+    fn paddle_starts_in_the_middle_of_levels_without_a_paddle_x() {
+        use bevy::ecs::system::RunSystemOnce;
+
+        let mut config = GameConfig::default();
+        // The computed brick grid doesn't say where the paddle starts either
+        config.levels.files.clear();
+        let mut world = World::new();
+        world.insert_resource(config);
+        world.init_resource::<CurrentLevel>();
+        world.init_resource::<LevelTransition>();
+        world.insert_resource(LevelStart { paddle_x: Some(-100.0), ball: None });
+        world.init_resource::<ActivePowerUps>();
+        world.init_resource::<CollisionTracker>();
+        let paddle = world.spawn((EntityType::Paddle, Transform::from_xyz(150.0, -250.0, 0.0))).id();
+
+        world.run_system_once(start_next_level).unwrap();
+
+        assert_eq!(*world.resource::<LevelStart>(), LevelStart::default());
+        assert_eq!(world.get::<Transform>(paddle).unwrap().translation, Vec3::new(0.0, -250.0, 0.0));
+    }
+}
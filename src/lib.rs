@@ -0,0 +1,1057 @@
+//! A simplified implementation of the classic game "Breakout".
+//!
+//! Demonstrates Bevy's stepping capabilities if compiled with the `bevy_debug_stepping` feature.
+//! Everything is added by `BreakoutPlugin`, see `examples/breakout.rs` for the plain game.
+//!
+//! Bounces only turn velocities that still head into what was hit. A pair can keep overlapping for
+//! a few ticks, and bouncing every tick would flip a ball back and forth inside the paddle.
+#![allow(dead_code,unused_variables,unused_parens)]
+#![allow(clippy::type_complexity, clippy::too_many_arguments)] // Bevy queries and systems trip these lints
+use std::path::PathBuf;
+
+use bevy::{ecs::system::EntityCommands, prelude::*, utils::HashSet};
+use collision::{ColliderShape, CollisionLayers};
+pub use collision::{Collision, CollisionPhase, Manifold};
+use config::{color, ArenaConfig, GameConfig, PaddleConfig};
+use lives::{Lives, LossZone};
+use scoring::{Combo, Points, ScoreBonus};
+use stash::StashableComponent;
+use states::{GameState, InGame};
+// mod stepping;
+pub mod bricks;
+pub mod broadphase;
+pub mod collision;
+pub mod config;
+pub mod editor;
+pub mod hot_reload;
+pub mod lasers;
+pub mod levels;
+pub mod lives;
+pub mod powerups;
+pub mod reset;
+pub mod scoring;
+pub mod stash;
+pub mod states;
+
+// We set the z-value of the ball to 1 so it renders on top in the case of overlapping sprites.
+const BALL_Z: f32 = 1.0;
+
+/// Runs the game. Add it alongside `DefaultPlugins` to embed Breakout in an app.
+pub struct BreakoutPlugin {
+    /// The `GameConfig` file, relative to the assets directory.
+    pub config_path: PathBuf,
+    /// Re-apply the config whenever its file changes, see `hot_reload`.
+    pub watch_config: bool,
+}
+
+impl Default for BreakoutPlugin {
+    fn default() -> Self {
+        BreakoutPlugin {
+            config_path: PathBuf::from(config::DEFAULT_CONFIG_PATH),
+            watch_config: true,
+        }
+    }
+}
+
+impl Plugin for BreakoutPlugin {
+    fn build(&self, app: &mut App) {
+        let config = config::load_or_default(&self.config_path);
+        app
+            // .add_plugins(
+            //     stepping::SteppingPlugin::default()
+            //         .add_schedule(Update)
+            //         .add_schedule(FixedUpdate)
+            //         .at(Val::Percent(35.0), Val::Percent(50.0)),
+            // )
+            .insert_resource(Score(0))
+            .insert_resource(Lives(config.lives.starting))
+            .insert_resource(ClearColor(color(config.arena.background_color)))
+            .insert_resource(config)
+            .add_event::<CollisionEvent>()
+            .init_resource::<collision::CollisionTracker>()
+            .init_resource::<powerups::ActivePowerUps>()
+            .init_resource::<lasers::LaserCooldown>()
+            .init_resource::<levels::CurrentLevel>()
+            .init_resource::<levels::LevelTransition>()
+            .init_resource::<levels::LevelStart>()
+            .add_event::<DestroyedEvent>()
+            .add_event::<ScoreBonus>()
+            .add_event::<reset::ResetWorld>()
+            .add_systems(Startup, setup)
+            .add_systems(OnEnter(InGame), start_game)
+            .add_systems(OnEnter(GameState::LevelComplete), levels::start_next_level)
+            .add_systems(
+                Update,
+                levels::finish_transition.run_if(in_state(GameState::LevelComplete)),
+            )
+            // Add our gameplay simulation systems to the fixed timestep schedule
+            // which runs at 64 Hz by default
+            .add_systems(
+                FixedUpdate,
+                (
+                    apply_velocity,
+                    collision::move_swept,
+                    move_controllable,
+                    bricks::patrol,
+                    collision::check_for_intersections,
+                    collision::track_collisions,
+                    collision::resolve_penetration,
+                    destroy_destroyables,
+                    bricks::explode,
+                    show_damage,
+                    scoring::award_points,
+                    scoring::award_bonuses,
+                    scoring::reset_combos,
+                    lives::lose_balls,
+                    process_bounces,
+                    (
+                        powerups::drop_capsules,
+                        powerups::catch_capsules,
+                        powerups::stick_balls,
+                        powerups::carry_stuck_balls,
+                        powerups::expire_power_ups,
+                        powerups::apply_power_ups,
+                        powerups::despawn_missed_capsules,
+                        lasers::fire_lasers,
+                        lasers::despawn_spent_projectiles,
+                    ),
+                    play_collision_sound,
+                    // play_collision_sound.run_if(on_event::<CollisionEvent>),
+                    (bricks::start_regrowing, despawn_destroyed, bricks::regrow),
+                    states::check_level_complete,
+                )
+                    // `chain`ing systems together runs them in order
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    reset::reset_on_key.run_if(not(in_state(GameState::Editor))),
+                    reset::reset_world,
+                    update_scoreboard,
+                    powerups::update_hud,
+                )
+                    .chain(),
+            );
+        states::add_states(app);
+        editor::add_editor(app);
+
+        if self.watch_config {
+            hot_reload::watch(app, config::asset_path(&self.config_path));
+        }
+    }
+}
+
+// Meta components
+// These can be stashed and restored without losing their data, see `stash`.
+
+#[derive(Component, Default)]
+pub struct Controllable;
+
+// Every destructor keeps its own combo, see `scoring`
+#[derive(Component)]
+#[require(Combo)]
+pub struct Destructor {
+    /// How many hit points each hit takes off a `Destructable`.
+    pub damage: u32,
+}
+
+impl Default for Destructor {
+    fn default() -> Self {
+        Destructor { damage: 1 }
+    }
+}
+
+/// Destroyed once `Destructor`s have taken all of its hit points.
+#[derive(Component)]
+pub struct Destructable {
+    pub hit_points: u32,
+}
+
+impl Default for Destructable {
+    fn default() -> Self {
+        Destructable { hit_points: 1 }
+    }
+}
+
+impl Destructable {
+    /// Takes `damage` off and returns whether that was the last of its hit points.
+    pub fn take_hit(&mut self, damage: u32) -> bool {
+        self.hit_points = self.hit_points.saturating_sub(damage);
+        self.hit_points == 0
+    }
+}
+
+// #[derive(Component)]
+// struct Ball;
+
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct Velocity(pub Vec2);
+
+#[derive(Component, Default)]
+pub struct Collider;
+
+/// How hard something moving is to knock off course when it hits something else that moves.
+/// Entities without one weigh `Mass::default()`.
+#[derive(Component, Debug, Clone, Copy, Deref, DerefMut)]
+pub struct Mass(pub f32);
+
+impl Default for Mass {
+    fn default() -> Self {
+        Mass(1.0)
+    }
+}
+
+/// How much of their closing speed two moving entities get back when they hit, from `0.0`
+/// (they stick together) to `1.0` (perfectly elastic). A pair uses the lower of the two.
+/// Entities without one use `Restitution::default()`.
+#[derive(Component, Debug, Clone, Copy, Deref, DerefMut)]
+pub struct Restitution(pub f32);
+
+impl Default for Restitution {
+    fn default() -> Self {
+        Restitution(1.0)
+    }
+}
+
+/// What kind of thing an entity is, so frontends like the sandbox can find every ball, brick, etc.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntityType {
+    Ball,
+    Paddle,
+    Wall,
+    Brick,
+    /// A falling power-up, see `powerups`.
+    Capsule,
+    /// A laser shot, see `lasers`.
+    Projectile,
+    Score,
+}
+
+impl EntityType {
+    pub const ALL: [EntityType; 7] = [
+        EntityType::Ball,
+        EntityType::Paddle,
+        EntityType::Wall,
+        EntityType::Brick,
+        EntityType::Capsule,
+        EntityType::Projectile,
+        EntityType::Score,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EntityType::Ball => "Ball",
+            EntityType::Paddle => "Paddle",
+            EntityType::Wall => "Wall",
+            EntityType::Brick => "Brick",
+            EntityType::Capsule => "Capsule",
+            EntityType::Projectile => "Projectile",
+            EntityType::Score => "Score",
+        }
+    }
+}
+
+/// Sent by `collision::track_collisions` for every pair of entities that touches, or just stopped.
+/// Most systems only react to `CollisionPhase::Started`, so a lasting contact counts once.
+#[derive(Event)]
+pub struct CollisionEvent {
+    pub e1: Entity,
+    pub e2: Entity,
+    pub hit_side_of_e1: Collision,
+    /// Its normal points from `e2` towards `e1`.
+    pub manifold: Manifold,
+    pub phase: CollisionPhase,
+}
+
+impl CollisionEvent {
+    pub fn started(&self) -> bool {
+        self.phase == CollisionPhase::Started
+    }
+}
+
+/// Sent when a `Destructor` destroys a `Destructable`.
+/// The destroyed entity is despawned at the end of the tick, after everything has reacted,
+/// unless it's a regenerating brick, see `bricks`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DestroyedEvent {
+    pub destructor: Entity,
+    pub destroyed: Entity,
+}
+
+// #[derive(Component)]
+// struct Brick;
+
+#[derive(Resource, Deref)]
+struct CollisionSound(Handle<AudioSource>);
+
+// Every ball is drawn with the same unit circle, scaled to its diameter
+#[derive(Resource, Deref)]
+struct BallMesh(Handle<Mesh>);
+
+// This bundle is a collection of the components that define a "wall" in our game
+#[derive(Bundle)]
+pub struct WallBundle {
+    // You can nest bundles inside of other bundles like this
+    // Allowing you to compose their functionality
+    sprite: Sprite,
+    transform: Transform,
+    collider: Collider,
+    layers: CollisionLayers,
+    entity_type: EntityType,
+}
+
+/// Which side of the arena is this wall located on?
+pub enum WallLocation {
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+impl WallLocation {
+    pub const ALL: [WallLocation; 4] = [
+        WallLocation::Left,
+        WallLocation::Right,
+        WallLocation::Bottom,
+        WallLocation::Top,
+    ];
+
+    /// Location of the *center* of the wall, used in `transform.translation()`
+    fn position(&self, arena: &ArenaConfig) -> Vec2 {
+        let center = Vec2::new(
+            (arena.left_wall + arena.right_wall) / 2.0,
+            (arena.bottom_wall + arena.top_wall) / 2.0,
+        );
+        match self {
+            WallLocation::Left => Vec2::new(arena.left_wall, center.y),
+            WallLocation::Right => Vec2::new(arena.right_wall, center.y),
+            WallLocation::Bottom => Vec2::new(center.x, arena.bottom_wall),
+            WallLocation::Top => Vec2::new(center.x, arena.top_wall),
+        }
+    }
+
+    /// (x, y) dimensions of the wall, used in `transform.scale()`
+    fn size(&self, arena: &ArenaConfig) -> Vec2 {
+        // A positive width and height is checked when the config is loaded
+        let arena_height = arena.top_wall - arena.bottom_wall;
+        let arena_width = arena.right_wall - arena.left_wall;
+
+        match self {
+            WallLocation::Left | WallLocation::Right => {
+                Vec2::new(arena.wall_thickness, arena_height + arena.wall_thickness)
+            }
+            WallLocation::Bottom | WallLocation::Top => {
+                Vec2::new(arena_width + arena.wall_thickness, arena.wall_thickness)
+            }
+        }
+    }
+}
+
+impl WallBundle {
+    // This "builder method" allows us to reuse logic across our wall entities,
+    // making our code easier to read and less prone to bugs when we change the logic
+    pub fn new(location: WallLocation, config: &GameConfig) -> WallBundle {
+        let arena = &config.arena;
+        WallBundle::from_rect(location.position(arena), location.size(arena), config)
+    }
+
+    /// A wall of any size, anywhere, for walls that aren't part of the arena.
+    pub fn from_rect(position: Vec2, size: Vec2, config: &GameConfig) -> WallBundle {
+        WallBundle {
+            sprite: Sprite::from_color(color(config.wall.color), Vec2::ONE),
+            transform: Transform {
+                // We need to convert our Vec2 into a Vec3, by giving it a z-coordinate
+                // This is used to determine the order of our sprites
+                translation: position.extend(0.0),
+                // The z-scale of 2D objects must always be 1.0,
+                // or their ordering will be affected in surprising ways.
+                // See https://github.com/bevyengine/bevy/issues/4149
+                scale: size.extend(1.0),
+                ..default()
+            },
+            // Walls get the rest of their archetype's components when spawned
+            collider: Collider,
+            layers: config.collision_layers(EntityType::Wall).unwrap_or_default(),
+            entity_type: EntityType::Wall,
+        }
+    }
+}
+
+// This resource tracks the game's score
+#[derive(Resource, Deref, DerefMut)]
+pub struct Score(pub usize);
+
+#[derive(Component)]
+struct ScoreboardUi;
+
+// Add the game's entities to our world
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    asset_server: Res<AssetServer>,
+    config: Res<GameConfig>,
+) {
+    // Camera
+    commands.spawn(Camera2d);
+
+    // Ball mesh
+    commands.insert_resource(BallMesh(meshes.add(Circle::default())));
+
+    // Sound
+    let ball_collision_sound = asset_server.load("sounds/breakout_collision.ogg");
+    commands.insert_resource(CollisionSound(ball_collision_sound));
+}
+
+// Runs whenever a new game starts from the main menu.
+// Everything spawned here is scoped to `InGame`, so it's gone again once the game ends.
+fn start_game(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    mut score: ResMut<Score>,
+    mut lives: ResMut<Lives>,
+    mut power_ups: ResMut<powerups::ActivePowerUps>,
+    mut current_level: ResMut<levels::CurrentLevel>,
+    play_test: Res<editor::PlayTest>,
+) {
+    **score = 0;
+    **lives = config.lives.starting;
+    power_ups.clear();
+    **current_level = 0;
+    spawn_entities(&mut commands, &config, editor::starting_level(&config, &play_test).as_ref());
+}
+
+/// Spawns every archetype in the config with its starting values and components,
+/// with the bricks and start positions of `level` if there is one.
+fn spawn_entities(commands: &mut Commands, config: &GameConfig, level: Option<&levels::Level>) {
+    let level_start = levels::LevelStart::of(level);
+    commands.insert_resource(level_start);
+    match level_start.paddle_x {
+        Some(paddle_x) => {
+            spawn_paddle_at(commands, config, Vec2::new(paddle_x, config.paddle_y()));
+        }
+        None => spawn_paddle(commands, config),
+    }
+    match level_start.ball {
+        Some(position) => {
+            let direction = Vec2::from(config.ball.serve_direction);
+            spawn_ball(commands, config, position, direction, color(config.ball.color));
+        }
+        None => spawn_balls(commands, config),
+    }
+    spawn_scoreboard(commands, config);
+    spawn_walls(commands, config);
+    spawn_bricks(commands, config, level);
+    powerups::spawn_hud(commands, config);
+}
+
+pub(crate) fn spawn_paddle(commands: &mut Commands, config: &GameConfig) {
+    spawn_paddle_at(commands, config, Vec2::new(0.0, config.paddle_y()));
+}
+
+/// Spawns a paddle centered on `position` rather than in its usual spot.
+pub fn spawn_paddle_at(commands: &mut Commands, config: &GameConfig, position: Vec2) -> Entity {
+    let paddle = &config.paddle;
+    let mut paddle_entity = commands.spawn((
+        Sprite::from_color(color(paddle.color), Vec2::ONE),
+        Transform {
+            translation: position.extend(0.0),
+            scale: Vec2::from(paddle.size).extend(1.0),
+            ..default()
+        },
+        EntityType::Paddle,
+        config.collision_layers(EntityType::Paddle).unwrap_or_default(),
+        StateScoped(InGame),
+    ));
+    insert_components(&mut paddle_entity, &paddle.components, Vec2::ZERO);
+    paddle_entity.id()
+}
+
+pub(crate) fn spawn_balls(commands: &mut Commands, config: &GameConfig) {
+    let ball = &config.ball;
+    for spawn in &ball.spawns {
+        spawn_ball(
+            commands,
+            config,
+            Vec2::from(spawn.position),
+            Vec2::from(spawn.direction),
+            color(spawn.color.unwrap_or(ball.color)),
+        );
+    }
+}
+
+/// Spawns a single ball at the configured speed. `direction` doesn't need to be normalized.
+pub fn spawn_ball(
+    commands: &mut Commands,
+    config: &GameConfig,
+    position: Vec2,
+    direction: Vec2,
+    ball_color: Color,
+) -> Entity {
+    let velocity = direction.normalize() * config.ball.speed;
+    spawn_ball_with_velocity(commands, config, position, velocity, ball_color)
+}
+
+/// Spawns a single ball moving at `velocity`, whatever the configured speed is.
+pub fn spawn_ball_with_velocity(
+    commands: &mut Commands,
+    config: &GameConfig,
+    position: Vec2,
+    velocity: Vec2,
+    ball_color: Color,
+) -> Entity {
+    let ball = &config.ball;
+    let mut ball_entity = commands.spawn((
+        Transform::from_translation(position.extend(BALL_Z))
+            .with_scale(Vec2::splat(ball.diameter).extend(1.)),
+        ColliderShape::Circle,
+        EntityType::Ball,
+        config.collision_layers(EntityType::Ball).unwrap_or_default(),
+        StateScoped(InGame),
+    ));
+    // Each ball gets its own material, so one can change color without the others
+    ball_entity.queue(move |entity: Entity, world: &mut World| {
+        let Some(mesh) = world.get_resource::<BallMesh>().map(|mesh| mesh.0.clone()) else {
+            return;
+        };
+        let Some(material) = world
+            .get_resource_mut::<Assets<ColorMaterial>>()
+            .map(|mut materials| materials.add(ball_color))
+        else {
+            return;
+        };
+        if let Ok(mut entity) = world.get_entity_mut(entity) {
+            entity.insert((Mesh2d(mesh), MeshMaterial2d(material)));
+        }
+    });
+    insert_components(&mut ball_entity, &ball.components, velocity);
+    if let Some(mass) = ball.mass {
+        ball_entity.insert(Mass(mass));
+    }
+    if let Some(restitution) = ball.restitution {
+        ball_entity.insert(Restitution(restitution));
+    }
+    let damage = ball.damage;
+    ball_entity.entry::<Destructor>().and_modify(move |mut destructor| destructor.damage = damage);
+    ball_entity.id()
+}
+
+pub(crate) fn spawn_scoreboard(commands: &mut Commands, config: &GameConfig) {
+    let scoreboard = &config.scoreboard;
+    let mut scoreboard_entity = commands.spawn((
+        Text::new("Score: "),
+        TextFont {
+            font_size: scoreboard.font_size,
+            ..default()
+        },
+        TextColor(color(scoreboard.text_color)),
+        ScoreboardUi,
+        EntityType::Score,
+        StateScoped(InGame),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(scoreboard.padding),
+            left: Val::Px(scoreboard.padding),
+            ..default()
+        },
+    ));
+    let font = TextFont {
+        font_size: scoreboard.font_size,
+        ..default()
+    };
+    scoreboard_entity.with_children(|parent| {
+        parent.spawn((TextSpan::default(), font.clone(), TextColor(color(scoreboard.score_color))));
+        parent.spawn((TextSpan::new("  Lives: "), font.clone(), TextColor(color(scoreboard.text_color))));
+        parent.spawn((TextSpan::default(), font, TextColor(color(scoreboard.score_color))));
+    });
+    insert_components(&mut scoreboard_entity, &scoreboard.components, Vec2::ZERO);
+}
+
+pub(crate) fn spawn_walls(commands: &mut Commands, config: &GameConfig) {
+    for location in WallLocation::ALL {
+        let is_loss_zone = matches!(location, WallLocation::Bottom) && config.arena.bottom_is_loss_zone;
+        let mut wall_entity = commands.spawn((WallBundle::new(location, config), StateScoped(InGame)));
+        insert_components(&mut wall_entity, &config.wall.components, Vec2::ZERO);
+        if is_loss_zone {
+            wall_entity.insert(LossZone);
+        }
+    }
+}
+
+/// Spawns a wall that isn't part of the arena. It is never a `LossZone`.
+pub fn spawn_wall(commands: &mut Commands, config: &GameConfig, position: Vec2, size: Vec2) -> Entity {
+    let mut wall_entity = commands.spawn((WallBundle::from_rect(position, size, config), StateScoped(InGame)));
+    insert_components(&mut wall_entity, &config.wall.components, Vec2::ZERO);
+    wall_entity.id()
+}
+
+/// Lays out `level`'s bricks, or fills the computed grid without one.
+pub(crate) fn spawn_bricks(commands: &mut Commands, config: &GameConfig, level: Option<&levels::Level>) {
+    if let Some(level) = level {
+        for (brick_position, brick_type) in level.brick_positions(config) {
+            spawn_brick_of_type(commands, config, brick_position, brick_type);
+        }
+        return;
+    }
+    for brick_position in config.brick_positions() {
+        spawn_brick(commands, config, brick_position);
+    }
+}
+
+pub fn spawn_brick(commands: &mut Commands, config: &GameConfig, position: Vec2) -> Entity {
+    spawn_brick_of_type(commands, config, position, None)
+}
+
+/// Spawns a brick of one of the config's `brick.types`, see `bricks`.
+/// `None`, or a type the config doesn't have, spawns a plain brick.
+pub fn spawn_brick_of_type(
+    commands: &mut Commands,
+    config: &GameConfig,
+    position: Vec2,
+    brick_type: Option<&str>,
+) -> Entity {
+    let brick = &config.brick;
+    let type_config = brick_type.and_then(|name| {
+        let type_config = brick.types.get(name);
+        if type_config.is_none() {
+            warn!("There's no brick type called {name:?}, spawning a plain brick instead");
+        }
+        type_config
+    });
+    let hit_points = type_config.and_then(|t| t.hit_points).unwrap_or(brick.hit_points);
+    let points = type_config.and_then(|t| t.points).unwrap_or(brick.points);
+    let components = type_config.and_then(|t| t.components.as_deref()).unwrap_or(&brick.components);
+    let mut brick_entity = commands.spawn((
+        Sprite {
+            color: config.brick_color(brick_type, hit_points),
+            ..default()
+        },
+        Transform {
+            translation: position.extend(0.0),
+            scale: Vec2::from(brick.size).extend(1.0),
+            ..default()
+        },
+        EntityType::Brick,
+        Points(points),
+        config.collision_layers(EntityType::Brick).unwrap_or_default(),
+        StateScoped(InGame),
+    ));
+    insert_components(&mut brick_entity, components, Vec2::ZERO);
+    if let (Some(name), Some(type_config)) = (brick_type, type_config) {
+        brick_entity.insert(bricks::BrickType(name.to_string()));
+        bricks::insert_behaviours(&mut brick_entity, config, type_config, position, hit_points);
+    }
+    brick_entity
+        .entry::<Destructable>()
+        .and_modify(move |mut destructable| destructable.hit_points = hit_points);
+    brick_entity.id()
+}
+
+/// Adds an archetype's toggleable components. `velocity` is only used if they include `Velocity`.
+pub(crate) fn insert_components(entity: &mut EntityCommands, components: &[StashableComponent], velocity: Vec2) {
+    for component in components {
+        match component {
+            StashableComponent::Velocity => entity.insert(Velocity(velocity)),
+            StashableComponent::Collider => entity.insert(Collider),
+            StashableComponent::Controllable => entity.insert(Controllable),
+            StashableComponent::Destructor => entity.insert(Destructor::default()),
+            StashableComponent::Destructable => entity.insert(Destructable::default()),
+        };
+    }
+}
+
+fn move_controllable(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut controllable_query: Query<&mut Transform, With<Controllable>>,
+    // mut paddle_transform: Single<&mut Transform, With<Controllable>>,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+) {
+    let arena = &config.arena;
+    let mut direction = 0.0;
+
+    if keyboard_input.pressed(KeyCode::ArrowLeft) {
+        direction -= 1.0;
+    }
+
+    if keyboard_input.pressed(KeyCode::ArrowRight) {
+        direction += 1.0;
+    }
+
+    for mut controllable_transform in &mut controllable_query {
+        // Calculate the new horizontal paddle position based on player input
+        let new_controllable_position =
+            controllable_transform.translation.x + direction * config.paddle.speed * time.delta_secs();
+
+        // TODO Reconsider later.
+        // Update the paddle position,
+        // making sure it doesn't cause the paddle to leave the arena
+        let left_bound = arena.left_wall + (arena.wall_thickness / 2.0) + controllable_transform.scale.x / 2.0;
+        let right_bound = arena.right_wall - (arena.wall_thickness / 2.0) - controllable_transform.scale.x / 2.0;
+
+        controllable_transform.translation.x = new_controllable_position.clamp(left_bound, right_bound);
+    }
+}
+
+// Entities with a `Collider` are moved by `collision::move_swept` instead
+fn apply_velocity(mut query: Query<(&mut Transform, &Velocity), Without<Collider>>, time: Res<Time>) {
+    for (mut transform, velocity) in &mut query {
+        transform.translation.x += velocity.x * time.delta_secs();
+        transform.translation.y += velocity.y * time.delta_secs();
+    }
+}
+
+fn update_scoreboard(
+    score: Res<Score>,
+    lives: Res<Lives>,
+    // There's no scoreboard in the main menu
+    score_root_query: Query<Entity, (With<ScoreboardUi>, With<Text>)>,
+    mut writer: TextUiWriter,
+) {
+    for score_root in &score_root_query {
+        *writer.text(score_root, 1) = score.to_string();
+        *writer.text(score_root, 3) = lives.to_string();
+    }
+}
+
+// Destroyed entities are only despawned at the end of the tick by `despawn_destroyed`,
+// so that systems reacting to a `DestroyedEvent` can still read them.
+fn destroy_destroyables(
+    mut events: EventReader<CollisionEvent>,
+    mut query: Query<(Entity, Option<&Destructor>, Option<&mut Destructable>), Or<(With<Destructor>, With<Destructable>)>>,
+    mut destroyed_events: EventWriter<DestroyedEvent>,
+) {
+    // A brick hit by two balls in the same tick is only destroyed once
+    let mut destroyed = HashSet::new();
+    for collision_event in events.read().filter(|event| event.started()) {
+        let result = query.get_many_mut([collision_event.e1, collision_event.e2]);
+        let maybe_entities = match result {
+            Ok(entities) => entities,
+            // TODO This is pretty magic to me, I think this is continuing the loop if it sees that the UI is intersecting with something.
+            // Could also use like query.get_many().unwrap_or_else(|error| {})
+            Err(error) => continue,
+            // Err(error) => panic!("Problem with collision: {error:?}"),
+        };
+        let [(entity1, maybe_destructor1, maybe_destructable1), (entity2, maybe_destructor2, maybe_destructable2)] = maybe_entities;
+        if let (Some(destructor), Some(mut destructable)) = (maybe_destructor1, maybe_destructable2) {
+            if destructable.take_hit(destructor.damage) && destroyed.insert(entity2) {
+                destroyed_events.send(DestroyedEvent { destructor: entity1, destroyed: entity2 });
+            }
+        }
+        if let (Some(destructor), Some(mut destructable)) = (maybe_destructor2, maybe_destructable1) {
+            if destructable.take_hit(destructor.damage) && destroyed.insert(entity1) {
+                destroyed_events.send(DestroyedEvent { destructor: entity2, destroyed: entity1 });
+            }
+        }
+    }
+}
+
+// Bricks change color as they lose hit points
+fn show_damage(
+    config: Res<GameConfig>,
+    mut query: Query<(&EntityType, &Destructable, Option<&bricks::BrickType>, &mut Sprite), Changed<Destructable>>,
+) {
+    for (entity_type, destructable, brick_type, mut sprite) in &mut query {
+        if *entity_type == EntityType::Brick {
+            let brick_type = brick_type.map(|brick_type| brick_type.0.as_str());
+            sprite.color = config.brick_color(brick_type, destructable.hit_points);
+        }
+    }
+}
+
+fn despawn_destroyed(
+    mut commands: Commands,
+    mut events: EventReader<DestroyedEvent>,
+    regenerating_query: Query<(), With<bricks::Regenerating>>,
+) {
+    // Regenerating bricks are hidden by `bricks::start_regrowing` instead
+    for event in events.read().filter(|event| !regenerating_query.contains(event.destroyed)) {
+        if let Some(entity) = commands.get_entity(event.destroyed) {
+            entity.despawn_recursive();
+        }
+    }
+}
+
+fn update_velocity_after_bounce(entity_velocity: &mut Velocity, collision_side: Collision) {
+    // Reflect the entity's velocity when it collides
+    let mut reflect_x = false;
+    let mut reflect_y = false;
+
+    // Reflect only if the velocity is in the opposite direction of the collision
+    // This prevents the ball from getting stuck inside the bar
+    match collision_side {
+        Collision::Left => reflect_x = entity_velocity.x > 0.0,
+        Collision::Right => reflect_x = entity_velocity.x < 0.0,
+        Collision::Top => reflect_y = entity_velocity.y < 0.0,
+        Collision::Bottom => reflect_y = entity_velocity.y > 0.0,
+    }
+
+    // Reflect velocity on the x-axis if we hit something on the x-axis
+    if reflect_x {
+        entity_velocity.x = -entity_velocity.x;
+    }
+
+    // Reflect velocity on the y-axis if we hit something on the y-axis
+    if reflect_y {
+        entity_velocity.y = -entity_velocity.y;
+    }
+}
+
+// Mirrors the velocity about `normal`, which points away from what was hit.
+fn reflect_about_normal(entity_velocity: &mut Velocity, normal: Vec2) {
+    let towards = entity_velocity.dot(normal);
+    if towards < 0.0 {
+        entity_velocity.0 -= 2.0 * towards * normal;
+    }
+}
+
+fn process_bounces(
+    // mut commands: Commands,
+    mut events: EventReader<CollisionEvent>,
+    mut query: Query<
+        (
+            Option<&mut Velocity>,
+            &Transform,
+            Option<&EntityType>,
+            Option<&Mass>,
+            Option<&Restitution>,
+            Option<&ColliderShape>,
+        ),
+        With<Collider>,
+    >,
+    config: Res<GameConfig>,
+) {
+    // Both have colliders
+    for collision_event in events.read().filter(|event| event.started()) {
+        // TODO These entities might not exist now.
+        let result = query.get_many_mut([collision_event.e1, collision_event.e2]);
+        // std::vec::Vec<std::option::Option<&mut Velocity>>
+        let maybe_entities  = match result {
+            Ok(entities) => entities,
+            Err(error) => continue,
+        };
+        // // [std::option::Option<&Velocity>
+        let [(mut maybe_velocity1, transform1, entity_type1, mass1, restitution1, shape1), (mut maybe_velocity2, transform2, entity_type2, mass2, restitution2, shape2)] = maybe_entities;
+        // Two moving entities trade momentum instead of each bouncing as if the other were a wall
+        if let (Some(velocity1), Some(velocity2)) = (maybe_velocity1.as_deref_mut(), maybe_velocity2.as_deref_mut()) {
+            let body1 = (*mass1.copied().unwrap_or_default(), *restitution1.copied().unwrap_or_default());
+            let body2 = (*mass2.copied().unwrap_or_default(), *restitution2.copied().unwrap_or_default());
+            exchange_momentum(velocity1, velocity2, body1, body2, collision_event.manifold.normal);
+            continue;
+        }
+        // Landing on top of a paddle aims the ball. A ball held by a sticky paddle has no velocity
+        // either, but it isn't a paddle
+        let is_paddle1 = entity_type1 == Some(&EntityType::Paddle);
+        let is_paddle2 = entity_type2 == Some(&EntityType::Paddle);
+        // Two boxes touch along an axis, anything round touches along the manifold normal
+        let is_circle = |shape: Option<&ColliderShape>| shape == Some(&ColliderShape::Circle);
+        let round_contact = is_circle(shape1) || is_circle(shape2);
+
+        if let Some(mut maybe_velocity1) = maybe_velocity1 {
+            if is_paddle2 && collision_event.hit_side_of_e1 == Collision::Top {
+                aim_off_paddle(&mut maybe_velocity1, transform1, transform2, &config.paddle);
+            } else if round_contact {
+                reflect_about_normal(&mut maybe_velocity1, collision_event.manifold.normal);
+            } else {
+                update_velocity_after_bounce(&mut maybe_velocity1, collision_event.hit_side_of_e1);
+            }
+        }
+        if let Some(mut maybe_velocity2) = maybe_velocity2 {
+            if is_paddle1 && collision_event.hit_side_of_e1.opposite() == Collision::Top {
+                aim_off_paddle(&mut maybe_velocity2, transform2, transform1, &config.paddle);
+            } else if round_contact {
+                reflect_about_normal(&mut maybe_velocity2, -collision_event.manifold.normal);
+            } else {
+                update_velocity_after_bounce(&mut maybe_velocity2, collision_event.hit_side_of_e1.opposite());
+            }
+        }
+
+    }
+}
+
+// Applies an impulse along `normal`, which points from the second body towards the first.
+// Each body is its `(mass, restitution)`.
+fn exchange_momentum(
+    velocity1: &mut Velocity,
+    velocity2: &mut Velocity,
+    (mass1, restitution1): (f32, f32),
+    (mass2, restitution2): (f32, f32),
+    normal: Vec2,
+) {
+    let closing_speed = (velocity1.0 - velocity2.0).dot(normal);
+    // Bodies already moving apart are left alone
+    if closing_speed >= 0.0 {
+        return;
+    }
+    let restitution = restitution1.min(restitution2);
+    let impulse = -(1.0 + restitution) * closing_speed / (mass1.recip() + mass2.recip());
+    velocity1.0 += normal * impulse / mass1;
+    velocity2.0 -= normal * impulse / mass2;
+}
+
+// Where the ball lands along the paddle picks the angle it leaves at, the middle sends it steepest.
+// Its speed stays the same.
+fn aim_off_paddle(ball_velocity: &mut Velocity, ball: &Transform, paddle: &Transform, paddle_config: &PaddleConfig) {
+    // A ball already rising off the paddle has been aimed
+    if ball_velocity.y >= 0.0 {
+        return;
+    }
+    let half_width = paddle.scale.x / 2.0;
+    let offset = ((ball.translation.x - paddle.translation.x) / half_width).clamp(-1.0, 1.0);
+    let (min, max) = (paddle_config.min_bounce_angle, paddle_config.max_bounce_angle);
+    let angle = (max - (max - min) * offset.abs()).to_radians();
+    // A ball landing dead center keeps heading the way it was
+    let direction_x = if offset == 0.0 { ball_velocity.x.signum() } else { offset.signum() };
+    ball_velocity.0 = Vec2::new(direction_x * angle.cos(), angle.sin()) * ball_velocity.length();
+}
+
+fn play_collision_sound(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    sound: Res<CollisionSound>,
+) {
+    // Play a sound once per frame if a collision started.
+    // Reading them all also prevents events staying active on the next frame.
+    if collision_events.read().filter(|event| event.started()).count() > 0 {
+        commands.spawn((AudioPlayer(sound.clone()), PlaybackSettings::DESPAWN));
+    }
+}
+
+/*
+- Event clearing? / velocity relfection handling possibly nonexistant entities
+*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PADDLE_X: f32 = 40.0;
+    const PADDLE_WIDTH: f32 = 120.0;
+
+    fn bounce(velocity: Vec2, ball_x: f32) -> Vec2 {
+        let mut velocity = Velocity(velocity);
+        let ball = Transform::from_xyz(ball_x, 0.0, 0.0);
+        let paddle = Transform::from_xyz(PADDLE_X, -10.0, 0.0).with_scale(Vec3::new(PADDLE_WIDTH, 20.0, 1.0));
+        aim_off_paddle(&mut velocity, &ball, &paddle, &PaddleConfig::default());
+        velocity.0
+    }
+
+    fn angle(velocity: Vec2) -> f32 {
+        velocity.y.atan2(velocity.x.abs()).to_degrees()
+    }
+
+    #[test]
+    fn paddle_bounce_keeps_speed() {
+        let incoming = Vec2::new(-120.0, -350.0);
+        for offset in [-1.5, -1.0, -0.4, 0.0, 0.3, 0.9] {
+            let outgoing = bounce(incoming, PADDLE_X + offset * PADDLE_WIDTH / 2.0);
+            assert!((outgoing.length() - incoming.length()).abs() < 1e-3, "{outgoing} at offset {offset}");
+        }
+    }
+
+    #[test]
+    fn middle_leaves_steepest_and_edges_flattest() {
+        let config = PaddleConfig::default();
+        let middle = bounce(Vec2::new(100.0, -300.0), PADDLE_X);
+        assert!((angle(middle) - config.max_bounce_angle).abs() < 1e-3);
+        for edge in [PADDLE_X - PADDLE_WIDTH / 2.0, PADDLE_X + PADDLE_WIDTH / 2.0, PADDLE_X + PADDLE_WIDTH] {
+            let outgoing = bounce(Vec2::new(100.0, -300.0), edge);
+            assert!((angle(outgoing) - config.min_bounce_angle).abs() < 1e-3, "{outgoing} off {edge}");
+        }
+    }
+
+    #[test]
+    fn side_hit_picks_the_direction() {
+        for incoming in [Vec2::new(200.0, -300.0), Vec2::new(-200.0, -300.0)] {
+            assert!(bounce(incoming, PADDLE_X - 30.0).x < 0.0);
+            assert!(bounce(incoming, PADDLE_X + 30.0).x > 0.0);
+        }
+        // Dead center keeps going the way it came
+        assert!(bounce(Vec2::new(-200.0, -300.0), PADDLE_X).x < 0.0);
+    }
+
+    #[test]
+    fn rising_ball_is_left_alone() {
+        assert_eq!(bounce(Vec2::new(100.0, 300.0), PADDLE_X + 30.0), Vec2::new(100.0, 300.0));
+    }
+
+    fn collide(velocity1: Vec2, velocity2: Vec2, body1: (f32, f32), body2: (f32, f32)) -> (Vec2, Vec2) {
+        let (mut velocity1, mut velocity2) = (Velocity(velocity1), Velocity(velocity2));
+        // The first body is on the left, so the normal points left
+        exchange_momentum(&mut velocity1, &mut velocity2, body1, body2, Vec2::NEG_X);
+        (velocity1.0, velocity2.0)
+    }
+
+    #[test]
+    fn momentum_is_conserved() {
+        let (before1, before2) = (Vec2::new(300.0, 40.0), Vec2::new(-100.0, -80.0));
+        let (mass1, mass2) = (1.0, 3.0);
+        let (after1, after2) = collide(before1, before2, (mass1, 0.6), (mass2, 0.8));
+        let momentum = |velocity1: Vec2, velocity2: Vec2| velocity1 * mass1 + velocity2 * mass2;
+        assert!(momentum(after1, after2).abs_diff_eq(momentum(before1, before2), 1e-3));
+        // Only the part along the normal changes
+        assert_eq!((after1.y, after2.y), (before1.y, before2.y));
+    }
+
+    #[test]
+    fn no_restitution_leaves_them_moving_together() {
+        let (after1, after2) = collide(Vec2::new(300.0, 0.0), Vec2::new(-100.0, 0.0), (1.0, 0.0), (3.0, 1.0));
+        // (1 * 300 + 3 * -100) / (1 + 3)
+        assert!(after1.abs_diff_eq(Vec2::ZERO, 1e-3) && after2.abs_diff_eq(Vec2::ZERO, 1e-3));
+    }
+
+    #[test]
+    fn full_restitution_swaps_equal_masses() {
+        let (after1, after2) = collide(Vec2::new(300.0, 0.0), Vec2::new(-100.0, 0.0), (2.0, 1.0), (2.0, 1.0));
+        assert!(after1.abs_diff_eq(Vec2::new(-100.0, 0.0), 1e-3));
+        assert!(after2.abs_diff_eq(Vec2::new(300.0, 0.0), 1e-3));
+    }
+
+    #[test]
+    fn separating_bodies_are_left_alone() {
+        let (after1, after2) = collide(Vec2::new(-300.0, 0.0), Vec2::new(100.0, 0.0), (1.0, 1.0), (1.0, 1.0));
+        assert_eq!((after1, after2), (Vec2::new(-300.0, 0.0), Vec2::new(100.0, 0.0)));
+    }
+
+    #[test]
+    fn reflection_keeps_speed_and_only_turns_incoming_velocities() {
+        // Hitting the top left of a round obstacle sends the ball back up and out to the left
+        let normal = Vec2::new(-1.0, 1.0).normalize();
+        let mut velocity = Velocity(Vec2::new(0.0, -300.0));
+        reflect_about_normal(&mut velocity, normal);
+        assert!(velocity.abs_diff_eq(Vec2::new(-300.0, 0.0), 1e-3));
+
+        let mut leaving = Velocity(Vec2::new(-100.0, 200.0));
+        reflect_about_normal(&mut leaving, normal);
+        assert_eq!(leaving.0, Vec2::new(-100.0, 200.0));
+    }
+
+    #[test]
+    fn only_paddles_aim_balls() {
+        use bevy::ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        world.insert_resource(GameConfig::default());
+        world.init_resource::<Events<CollisionEvent>>();
+        let incoming = Vec2::new(100.0, -300.0);
+        let mut land_on = |entity_type: EntityType| {
+            let ball = world.spawn((Collider, Transform::from_xyz(30.0, 20.0, 0.0), Velocity(incoming))).id();
+            // Neither a paddle nor a held ball moves on its own
+            let target = world.spawn((Collider, Transform::default().with_scale(Vec3::new(120.0, 20.0, 1.0)), entity_type, Controllable)).id();
+            world.send_event(CollisionEvent {
+                e1: ball,
+                e2: target,
+                hit_side_of_e1: Collision::Top,
+                manifold: Manifold { normal: Vec2::Y, depth: 1.0, point: Vec2::new(30.0, 10.0) },
+                phase: CollisionPhase::Started,
+            });
+            world.run_system_once(process_bounces).unwrap();
+            world.resource_mut::<Events<CollisionEvent>>().clear();
+            world.get::<Velocity>(ball).unwrap().0
+        };
+
+        assert_eq!(land_on(EntityType::Ball), Vec2::new(100.0, 300.0));
+        assert_ne!(land_on(EntityType::Paddle), Vec2::new(100.0, 300.0));
+    }
+}
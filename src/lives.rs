@@ -0,0 +1,187 @@
+//! Lives, and losing balls to the bottom of the arena.
+//!
+//! A ball that touches a `LossZone` is removed. Losing a ball only costs a life once no other
+//! balls are left, at which point a new one is served from the level's ball position, or above the
+//! paddle if it doesn't have one. Losing the last life moves
+//! the game to `GameState::GameOver`.
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    config::{color, GameConfig},
+    levels::LevelStart,
+    spawn_ball,
+    states::GameState,
+    CollisionEvent, EntityType,
+};
+
+/// How far above the paddle a served ball starts.
+const SERVE_GAP: f32 = 10.0;
+
+/// Balls touching this are lost. Given to the bottom wall unless the config says otherwise.
+#[derive(Component, Default)]
+pub struct LossZone;
+
+/// How many more times the player can run out of balls.
+#[derive(Resource, Debug, Deref, DerefMut)]
+pub struct Lives(pub usize);
+
+pub(crate) fn lose_balls(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    loss_zone_query: Query<(), With<LossZone>>,
+    entity_query: Query<(Entity, &EntityType, &Transform)>,
+    mut lives: ResMut<Lives>,
+    mut next_state: ResMut<NextState<GameState>>,
+    level_start: Res<LevelStart>,
+    config: Res<GameConfig>,
+) {
+    // Several balls can be lost in the same tick, and one ball can touch the zone more than once
+    let mut lost = HashSet::new();
+    for event in collision_events.read().filter(|event| event.started()) {
+        for (entity, other) in [(event.e1, event.e2), (event.e2, event.e1)] {
+            let is_ball = matches!(entity_query.get(entity), Ok((_, EntityType::Ball, _)));
+            if is_ball && loss_zone_query.contains(other) && lost.insert(entity) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+    if lost.is_empty() {
+        return;
+    }
+
+    let balls_left = entity_query
+        .iter()
+        .filter(|(entity, entity_type, _)| **entity_type == EntityType::Ball && !lost.contains(entity))
+        .count();
+    if balls_left > 0 {
+        return;
+    }
+
+    **lives = lives.saturating_sub(1);
+    if **lives == 0 {
+        next_state.set(GameState::GameOver);
+        return;
+    }
+    let paddle_x = entity_query
+        .iter()
+        .find(|(_, entity_type, _)| **entity_type == EntityType::Paddle)
+        .map_or(0.0, |(_, _, transform)| transform.translation.x);
+    serve_ball(&mut commands, &config, level_start.ball, paddle_x);
+}
+
+/// Spawns a ball at `position`, or just above a paddle at `paddle_x` without one, heading in
+/// `BallConfig::serve_direction`.
+pub(crate) fn serve_ball(
+    commands: &mut Commands,
+    config: &GameConfig,
+    position: Option<Vec2>,
+    paddle_x: f32,
+) -> Entity {
+    let position = position.unwrap_or_else(|| {
+        Vec2::new(
+            paddle_x,
+            config.paddle_y() + config.paddle.size[1] / 2.0 + config.ball.diameter / 2.0 + SERVE_GAP,
+        )
+    });
+    spawn_ball(
+        commands,
+        config,
+        position,
+        Vec2::from(config.ball.serve_direction),
+        color(config.ball.color),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{Collision, CollisionPhase, Manifold};
+
+    fn world(lives: usize) -> World {
+        let mut world = World::new();
+        world.insert_resource(GameConfig::default());
+        world.insert_resource(Lives(lives));
+        world.init_resource::<NextState<GameState>>();
+        world.init_resource::<LevelStart>();
+        world.init_resource::<Events<CollisionEvent>>();
+        world
+    }
+
+    fn lose(world: &mut World, ball: Entity, loss_zone: Entity) {
+        world.send_event(CollisionEvent {
+            e1: ball,
+            e2: loss_zone,
+            hit_side_of_e1: Collision::Top,
+            manifold: Manifold { normal: Vec2::Y, depth: 1.0, point: Vec2::ZERO },
+            phase: CollisionPhase::Started,
+        });
+        world.run_system_once(lose_balls).unwrap();
+    }
+
+    fn balls(world: &mut World) -> Vec<Vec2> {
+        let mut query = world.query::<(&EntityType, &Transform)>();
+        query
+            .iter(world)
+            .filter(|(entity_type, _)| **entity_type == EntityType::Ball)
+            .map(|(_, transform)| transform.translation.truncate())
+            .collect()
+    }
+
+    #[test]
+    fn other_balls_keep_the_life() {
+        let mut world = world(3);
+        let loss_zone = world.spawn(LossZone).id();
+        let ball = world.spawn((EntityType::Ball, Transform::default())).id();
+        world.spawn((EntityType::Ball, Transform::from_xyz(50.0, 0.0, 0.0)));
+
+        lose(&mut world, ball, loss_zone);
+
+        assert_eq!(world.resource::<Lives>().0, 3);
+        assert_eq!(balls(&mut world), vec![Vec2::new(50.0, 0.0)]);
+    }
+
+    #[test]
+    fn last_ball_costs_a_life_and_serves_another() {
+        let mut world = world(3);
+        let loss_zone = world.spawn(LossZone).id();
+        world.spawn((EntityType::Paddle, Transform::from_xyz(-120.0, 0.0, 0.0)));
+        let ball = world.spawn((EntityType::Ball, Transform::default())).id();
+
+        lose(&mut world, ball, loss_zone);
+
+        assert_eq!(world.resource::<Lives>().0, 2);
+        let served = balls(&mut world);
+        assert_eq!(served.len(), 1);
+        assert_eq!(served[0].x, -120.0);
+        assert!(served[0].y > world.resource::<GameConfig>().paddle_y());
+    }
+
+    #[test]
+    fn level_ball_start_is_served_from() {
+        let mut world = world(3);
+        let start = Vec2::new(60.0, -150.0);
+        world.insert_resource(LevelStart { paddle_x: None, ball: Some(start) });
+        let loss_zone = world.spawn(LossZone).id();
+        world.spawn((EntityType::Paddle, Transform::from_xyz(-120.0, 0.0, 0.0)));
+        let ball = world.spawn((EntityType::Ball, Transform::default())).id();
+
+        lose(&mut world, ball, loss_zone);
+
+        assert_eq!(balls(&mut world), vec![start]);
+    }
+
+    #[test]
+    fn last_life_ends_the_game() {
+        let mut world = world(1);
+        let loss_zone = world.spawn(LossZone).id();
+        let ball = world.spawn((EntityType::Ball, Transform::default())).id();
+
+        lose(&mut world, ball, loss_zone);
+
+        assert_eq!(world.resource::<Lives>().0, 0);
+        assert!(balls(&mut world).is_empty());
+        assert!(matches!(*world.resource::<NextState<GameState>>(), NextState::Pending(GameState::GameOver)));
+    }
+}
@@ -1,91 +1,33 @@
+//! A sandbox on top of `BreakoutPlugin` for toggling components on live entities and spawning new ones at the cursor.
 #![allow(dead_code, unused)] // TODO remove this
-use bevy::{
-    core::FixedTimestep,
-    math::{const_vec2, const_vec3},
-    prelude::*,
-    sprite::collide_aabb::{collide, Collision},
-    winit::WinitSettings,
+#![allow(clippy::type_complexity, clippy::too_many_arguments)] // Bevy queries and systems trip these lints
+use bevy::{app::AppExit, prelude::*, window::PrimaryWindow, winit::WinitSettings};
+use breakout2::{
+    config::{color, GameConfig},
+    reset::ResetWorld,
+    spawn_ball_with_velocity, spawn_brick, spawn_paddle_at, spawn_wall,
+    stash::{StashCommandsExt, StashableComponent},
+    states::InGame,
+    BreakoutPlugin, EntityType,
 };
 // use arrayvec::ArrayVec;
-// Defines the amount of time that should elapse between each physics step.
-const TIME_STEP: f32 = 1.0 / 60.0;
-
-// Paddle size/movement
-// These constants are defined in `Transform` units.
-// Using the default 2D camera they correspond 1:1 with screen pixels.
-// The `const_vec3!` macros are needed as functions that operate on floats cannot be constant in Rust.
-const PADDLE_SIZE: Vec3 = const_vec3!([120.0, 20.0, 0.0]);
-const GAP_BETWEEN_PADDLE_AND_FLOOR: f32 = 60.0;
-const PADDLE_SPEED: f32 = 500.0;
-// How close can the paddle get to the wall
-const PADDLE_PADDING: f32 = 10.0;
-const PADDLE_Y: f32 = BOTTOM_WALL + GAP_BETWEEN_PADDLE_AND_FLOOR;
-
-// Wall constants
-const WALL_THICKNESS: f32 = 10.0;
-// x coordinates
-const LEFT_WALL: f32 = -450.;
-const RIGHT_WALL: f32 = 450.;
-// y coordinates
-const BOTTOM_WALL: f32 = -300.;
-const TOP_WALL: f32 = 300.;
-
-// Ball constants
-// We set the z-value of the ball to 1 so it renders on top in the case of overlapping sprites.
-const BALL_STARTING_POSITION: Vec3 = const_vec3!([0.0, -50.0, 1.0]);
-const BALL_SIZE: Vec3 = const_vec3!([30.0, 30.0, 0.0]);
-const BALL_SPEED: f32 = 400.0;
-const INITIAL_BALL_DIRECTION: Vec2 = const_vec2!([0.5, -0.5]);
-
-// Colors
-const BACKGROUND_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
-const PADDLE_COLOR: Color = Color::rgb(0.3, 0.3, 0.7);
-const BALL_COLOR: Color = Color::rgb(1.0, 0.5, 0.5);
-const BRICK_COLOR: Color = Color::rgb(0.5, 0.5, 1.0);
-const WALL_COLOR: Color = Color::rgb(0.8, 0.8, 0.8);
-const TEXT_COLOR: Color = Color::rgb(0.5, 0.5, 1.0);
-const SCORE_COLOR: Color = Color::rgb(1.0, 0.5, 0.5);
 
 // Buttons
-const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
-const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
-const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
+const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
+const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.75, 0.35);
 
 // Meta
-const COMPONENTS: &'static [&'static str] = &["Sprite", "Transform", "Collider", "Velocity", "Controllable", "Destructor", "Destructable"];
-// const ENTITY_TYPES: &'static [&'static str] = &["Ball", "Paddle", "Wall", "Brick", "Score"];
-// const ENTITY_TYPES: &'static [Component; 5] = &[Ball, Paddle, Wall, Brick, Score];
-// const xs: [dyn any; 5]= [Ball, Paddle, Wall, Brick, Score];
+// Sprite and Transform used to be listed here too, but only stashable components can be toggled.
+const COMPONENTS: [StashableComponent; 5] = StashableComponent::ALL;
 
-// let mut p = MaybeUninit::<[Component; 5]>::uninit();
+const ENTITY_TYPES: [EntityType; 7] = EntityType::ALL;
 
-// Meta Components
-#[derive(Component)]
-struct Ball;
-#[derive(Component)]
-struct Paddle;
-#[derive(Component)]
-struct Wall;
-#[derive(Component)]
-struct Brick;
-#[derive(Component)]
-struct Score;
-
-#[derive(Clone, Copy)]
-enum EntityType {
-    Ball,
-    Paddle,
-    Wall,
-    Brick,
-    Score
-}
-const ENTITY_TYPES: [EntityType; 5] = [
-    EntityType::Ball,
-    EntityType::Paddle,
-    EntityType::Wall,
-    EntityType::Brick,
-    EntityType::Score
-];
+// Spawn tool
+const SPAWNABLE_TYPES: [EntityType; 4] = [EntityType::Ball, EntityType::Paddle, EntityType::Brick, EntityType::Wall];
+// A ball dragged 100 px starts at 200 px/s
+const DRAG_TO_VELOCITY: f32 = 2.0;
+const DRAG_PREVIEW_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
 
 // pub struct HelloPlugin;
 // impl Plugin for HelloPlugin {
@@ -96,275 +38,364 @@ const ENTITY_TYPES: [EntityType; 5] = [
 
 fn main() {
     App::new()
-        .add_plugins(DefaultPlugins)
+        .add_plugins((DefaultPlugins, BreakoutPlugin::default()))
         // // Only run the app when there is user input. This will significantly reduce CPU/GPU use but be unplayable.
         // .insert_resource(WinitSettings::desktop_app())
-        // .add_plugin(HelloPlugin)
-        .insert_resource(ClearColor(BACKGROUND_COLOR))
-        .add_system(bevy::input::system::exit_on_esc_system)
-        .add_startup_system(setup)
-        .add_system_set(
-            SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(TIME_STEP as f64))
-                .with_system(move_controllable)
-                .with_system(button_system)
+        // .add_plugins(HelloPlugin)
+        .init_resource::<SpawnTool>()
+        .add_systems(Startup, setup)
+        .add_systems(Update, (exit_on_esc, button_system, reset_button_system, update_button_colors).chain())
+        .add_systems(
+            Update,
+            (
+                spawn_tool_button_system,
+                update_spawn_tool_button_colors,
+                spawn_at_cursor.run_if(in_state(InGame)),
+            )
+                .chain(),
         )
         .run();
 }
 
-#[derive(Component)]
-struct Collider;
-
-#[derive(Component)]
-struct Controllable;
-
-#[derive(Component)]
-struct Destructor;
-
-#[derive(Component)]
-struct Destructable;
-
-#[derive(Component, Deref, DerefMut)]
-struct Velocity(Vec2);
-
-fn root() -> NodeBundle {
-    NodeBundle {
-        style: Style {
-            justify_content: JustifyContent::Center,
-            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-            // // This doesn't seem to do anything
-            // align_items: AlignItems::Center,
-            ..default()
-        },
+fn root() -> Node {
+    Node {
+        justify_content: JustifyContent::Center,
+        width: Val::Percent(100.0),
+        height: Val::Percent(100.0),
+        // // This doesn't seem to do anything
+        // align_items: AlignItems::Center,
         ..default()
     }
 }
 
-fn menu_background() -> NodeBundle {
-    NodeBundle {
-        style: Style {
-            // Aligns columns to top
-            align_self: bevy::ui::AlignSelf::FlexEnd,
-            align_items: bevy::ui::AlignItems::FlexEnd,
-            // // Defines how flexbox items are ordered within a flexbox
-            // flex_direction: bevy::ui::FlexDirection::Column,
-            // The lower the padding, the farther apart the buttons get? 
-            // margin: Rect::all(Val::Px(200.0)),
-            // The padding of the node
-            // padding: Rect::all(Val::Px(1.0)),
-            size: Size::new(Val::Percent(50.0), Val::Percent(50.0)),
-            // Spaces columns apart
-            justify_content: JustifyContent::SpaceBetween,
-            ..default()
-        },
-        color: Color::NONE.into(),
-        // color: UiColor(Color::rgb(0.5, 0.5, 0.5)),
+fn menu_background() -> Node {
+    Node {
+        // Aligns columns to top
+        align_self: AlignSelf::FlexEnd,
+        align_items: AlignItems::FlexEnd,
+        // // Defines how flexbox items are ordered within a flexbox
+        // flex_direction: FlexDirection::Column,
+        width: Val::Percent(50.0),
+        height: Val::Percent(50.0),
+        // Spaces columns apart
+        justify_content: JustifyContent::SpaceBetween,
         ..default()
     }
 }
 
-fn column() -> NodeBundle {
-    NodeBundle {
-        style: Style {
-            flex_direction: bevy::ui::FlexDirection::Column,
-            justify_content: JustifyContent::SpaceBetween,
-            // 0% by default
-            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-            ..default()
-        },
-        color: Color::NONE.into(),
+fn column() -> Node {
+    Node {
+        flex_direction: FlexDirection::Column,
+        justify_content: JustifyContent::SpaceBetween,
+        // 0% by default
+        width: Val::Percent(100.0),
+        height: Val::Percent(100.0),
         ..default()
     }
 }
 
+/// Which entities a button acts on, and which of their components it toggles.
 #[derive(Component)]
 struct MetaEntityType { entity_type: EntityType }
 
-#[derive(Bundle)]
-struct CustomButtonBundle {
-    #[bundle]
-    button_bundle: ButtonBundle,
-    meta_entity_type: MetaEntityType,
+#[derive(Component)]
+struct MetaComponent { component: StashableComponent }
+
+fn button(entity_type: EntityType, component: StashableComponent) -> impl Bundle {
+    (
+        Button,
+        Node {
+            width: Val::Px(100.0),
+            height: Val::Px(40.0),
+            // center button
+            margin: UiRect::horizontal(Val::Auto),
+            ..default()
+        },
+        BackgroundColor(NORMAL_BUTTON),
+        MetaEntityType { entity_type },
+        MetaComponent { component },
+    )
 }
 
-fn button(meta_entity_type: EntityType) -> CustomButtonBundle {
-    CustomButtonBundle {
-        meta_entity_type: MetaEntityType {
-            entity_type: meta_entity_type
-        },
-        button_bundle: ButtonBundle {
-            style: Style {
-                // flex_direction: FlexDirection::Row,
-                size: Size::new(Val::Px(100.0), Val::Px(40.0)),
-                // center button
-                // margin: Rect::all(Val::Auto),
-                margin: Rect {left: Val::Auto, right: Val::Auto, bottom: Val::Px(0.0), top: Val::Px(0.0)},
-                // // horizontally center child text
-                // justify_content: JustifyContent::Center,
-                // // vertically center child text
-                // align_items: AlignItems::FlexEnd,
-                ..default()
-            },
-            color: NORMAL_BUTTON.into(),
+#[derive(Component)]
+struct ResetButton;
+
+fn reset_button() -> impl Bundle {
+    (
+        Button,
+        Node {
+            width: Val::Px(100.0),
+            height: Val::Px(40.0),
             ..default()
-        }
-    }
+        },
+        BackgroundColor(NORMAL_BUTTON),
+        ResetButton,
+    )
 }
 
-fn button_text(asset_server: &Res<AssetServer>, text_value:String) -> TextBundle {
-    TextBundle {
-        text: Text::with_section(
-            text_value,
-            TextStyle {
-                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                font_size: 20.0,
-                color: Color::rgb(0.9, 0.9, 0.9),
-            },
-            Default::default(),
-        ),
+// Sits at the top, across from the toggle buttons
+fn toolbar() -> Node {
+    Node {
+        flex_direction: FlexDirection::Column,
+        align_self: AlignSelf::FlexStart,
         ..default()
     }
 }
 
-// only run once, since it is only a startup_system
+/// Selects which archetype clicking in the arena spawns.
+#[derive(Component)]
+struct SpawnToolButton { entity_type: EntityType }
+
+fn spawn_tool_button(entity_type: EntityType) -> impl Bundle {
+    (
+        Button,
+        Node {
+            width: Val::Px(100.0),
+            height: Val::Px(40.0),
+            ..default()
+        },
+        BackgroundColor(NORMAL_BUTTON),
+        SpawnToolButton { entity_type },
+    )
+}
+
+/// The archetype picked with the spawn tool buttons, if any, and where the current drag started.
+#[derive(Resource, Default)]
+struct SpawnTool {
+    selected: Option<EntityType>,
+    drag_start: Option<Vec2>,
+}
+
+// The default font is used because fonts/FiraSans-Bold.ttf isn't shipped with the repo, see the gotchas below.
+fn button_text(text_value: &str) -> impl Bundle {
+    (
+        Text::new(text_value),
+        TextFont {
+            font_size: 20.0,
+            ..default()
+        },
+        TextColor(Color::srgb(0.9, 0.9, 0.9)),
+    )
+}
+
+// only run once, since it is only a startup system
+// The game itself, camera included, is spawned by `BreakoutPlugin`.
 fn setup(
     // a reference to a Commands struct. The Commands struct can be used to spawn Bundles and to add Components.
     mut commands: Commands,
-    // // an instance of a collection of ColorMaterials. Material is used to paint 2D or 3D objects on screen and ColorMaterial is the simplest Material as it only supports a single color as oposed to, for example, textures.
-    // mut materials: ResMut<Assets<ColorMaterial>>, 
-    // an instance of AssetServer. The AssetServer will be used in the last section of this tutorial to load the font we will use to display the game score.
-    asset_server: Res<AssetServer>, 
 ) {
-    // Cameras
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-    commands.spawn_bundle(UiCameraBundle::default());
-
     // UI
     commands
-        .spawn_bundle(root())
+        .spawn(root())
         .with_children(|parent| {
-            parent.spawn_bundle(menu_background())
+            parent.spawn(menu_background())
             .with_children(|parent| {
                 for entity_type in ENTITY_TYPES {
-                    parent.spawn_bundle(column())
+                    parent.spawn(column())
                     .with_children(|parent| {
+                        parent.spawn(button_text(entity_type.name()));
                         for component in COMPONENTS {
-                            parent.spawn_bundle(button(entity_type))
+                            parent.spawn(button(entity_type, component))
                             .with_children(|parent| {
-                                parent.spawn_bundle(button_text(&asset_server, component.to_string()));
+                                parent.spawn(button_text(component.name()));
                             });
                         }
                     });
                 }
             });
+            parent.spawn(toolbar())
+            .with_children(|parent| {
+                parent.spawn(reset_button())
+                .with_children(|parent| {
+                    parent.spawn(button_text("Reset"));
+                });
+                parent.spawn(button_text("Spawn"));
+                for entity_type in SPAWNABLE_TYPES {
+                    parent.spawn(spawn_tool_button(entity_type))
+                    .with_children(|parent| {
+                        parent.spawn(button_text(entity_type.name()));
+                    });
+                }
+            });
         });
+}
 
-    // Next we spawn a sprite to represent our paddle. A sprite is usually a simple little graphical 2D object, like a tree or a player character (like 2D Mario for example).
-    // The spawn() creates the empty entity and we're adding components. Probably still should use a bundle. Here we're inserting a Paddle component, a SpriteBundle, t
-    commands
-        .spawn() // Create an empty Entity and return an EntityCommands builder for it
-        .insert_bundle(SpriteBundle {
-            transform: Transform {
-                translation: Vec3::new(0.0, PADDLE_Y, 0.0),
-                scale: PADDLE_SIZE,
-                ..default()
-            },
-            sprite: Sprite {
-                color: PADDLE_COLOR,
-                ..default()
-            },
-            ..default()
-        })
-        .insert(Collider)
-        .insert(Controllable);
-
+fn exit_on_esc(keyboard_input: Res<ButtonInput<KeyCode>>, mut exit: EventWriter<AppExit>) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        exit.send(AppExit::Success);
+    }
 }
 
+// A button is "on" when every live entity of its type has the component.
+// Clicking an "on" button stashes the component off all of them, clicking an "off" one restores it to all of them.
 fn button_system(
-    mut interaction_query: Query<
-        (&Interaction, &mut UiColor, &Children),
-        (Changed<Interaction>, With<Button>), // This triggers every frame even though it has a Changed filter?
+    mut commands: Commands,
+    interaction_query: Query<
+        (&Interaction, &MetaEntityType, &MetaComponent),
+        (Changed<Interaction>, With<Button>),
     >,
-    mut text_query: Query<&mut Text>,
+    entity_query: Query<(EntityRef, &EntityType)>,
 ) {
-    // println!("{subject}",
-    //          subject="here");
-    for (interaction, mut color, children) in interaction_query.iter_mut() {
-        // text_query is querying for Text. But in this case it's being passed children[0].
-        let mut text = text_query.get_mut(children[0]).unwrap();
-        match *interaction {
-            Interaction::Clicked => {
-                // let immutable_color =  &*color;
-                if (color.0 == NORMAL_BUTTON.into()) {
-                    *color = PRESSED_BUTTON.into();
-                } else {
-                    *color = NORMAL_BUTTON.into();
-                }
-                
-            }
-            Interaction::Hovered => {
-                // text.sections[0].value = "Hover".to_string();
-                // *color = HOVERED_BUTTON.into();
-            }
-            Interaction::None => {
-                // text.sections[0].value = "Button".to_string();
-                // *color = NORMAL_BUTTON.into();
+    for (interaction, meta_entity_type, meta_component) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let entities = entity_query
+            .iter()
+            .filter(|(_, entity_type)| **entity_type == meta_entity_type.entity_type)
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        let all_present = is_on(&entities, meta_component.component);
+        for entity in entities {
+            if all_present {
+                commands.entity(entity.id()).stash_component(meta_component.component);
+            } else {
+                commands.entity(entity.id()).restore_or_default_component(meta_component.component);
             }
         }
     }
 }
 
-// fn button_system(
-//     mut interaction_query: Query<
-//         (&Interaction, &mut UiColor, &Children),
-//         (Changed<Interaction>, With<Button>),
-//     >,
-//     mut text_query: Query<&mut Text>,
-// ) {
-//     for (interaction, mut color, children) in interaction_query.iter_mut() {
-//         let mut text = text_query.get_mut(children[0]).unwrap();
-//         match *interaction {
-//             Interaction::Clicked => {
-//                 text.sections[0].value = "Press".to_string();
-//                 *color = PRESSED_BUTTON.into();
-//             }
-//             Interaction::Hovered => {
-//                 text.sections[0].value = "Hover".to_string();
-//                 *color = HOVERED_BUTTON.into();
-//             }
-//             Interaction::None => {
-//                 text.sections[0].value = "Button".to_string();
-//                 *color = NORMAL_BUTTON.into();
-//             }
-//         }
-//     }
-// }
+fn reset_button_system(
+    mut interaction_query: Query<(&Interaction, &mut BackgroundColor), (Changed<Interaction>, With<ResetButton>)>,
+    mut resets: EventWriter<ResetWorld>,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        *color = BackgroundColor(match interaction {
+            Interaction::Pressed => PRESSED_BUTTON,
+            Interaction::Hovered => HOVERED_BUTTON,
+            Interaction::None => NORMAL_BUTTON,
+        });
+        if *interaction == Interaction::Pressed {
+            resets.send_default();
+        }
+    }
+}
 
-// I believe this function is called every frame. Updates the paddle position keeping it within the bounds if the left or right keys are pressed.
-fn move_controllable(
-    keyboard_input: Res<Input<KeyCode>>, // an instance of the default Input resource under the name keyboard_input.
-    mut query: Query<&mut Transform, With<Controllable>>, // an instance of a Query that references our Paddle and its Transform.
+// The colour is read back from the entities every frame rather than flipped on click,
+// so it stays correct for entities spawned afterwards or changed by other systems.
+fn update_button_colors(
+    mut button_query: Query<(&Interaction, &MetaEntityType, &MetaComponent, &mut BackgroundColor), With<Button>>,
+    entity_query: Query<(EntityRef, &EntityType)>,
 ) {
-    let mut entity_transform = query.single_mut();
-    let mut direction = 0.0;
-
-    if keyboard_input.pressed(KeyCode::Left) {
-        direction -= 1.0;
-    } else if keyboard_input.pressed(KeyCode::Right) {
-        direction += 1.0;
-    } else {
-        return
+    for (interaction, meta_entity_type, meta_component, mut color) in &mut button_query {
+        let entities = entity_query
+            .iter()
+            .filter(|(_, entity_type)| **entity_type == meta_entity_type.entity_type)
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        let new_color = if is_on(&entities, meta_component.component) {
+            PRESSED_BUTTON
+        } else if *interaction == Interaction::Hovered {
+            HOVERED_BUTTON
+        } else {
+            NORMAL_BUTTON
+        };
+        color.set_if_neq(BackgroundColor(new_color));
     }
+}
 
-    // Calculate the new horizontal paddle position based on player input
-    let new_entity_position = entity_transform.translation.x + direction * PADDLE_SPEED * TIME_STEP;
+// Clicking the selected tool again puts it away
+fn spawn_tool_button_system(
+    interaction_query: Query<(&Interaction, &SpawnToolButton), Changed<Interaction>>,
+    mut spawn_tool: ResMut<SpawnTool>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        spawn_tool.selected = if spawn_tool.selected == Some(button.entity_type) {
+            None
+        } else {
+            Some(button.entity_type)
+        };
+        spawn_tool.drag_start = None;
+    }
+}
+
+fn update_spawn_tool_button_colors(
+    mut button_query: Query<(&Interaction, &SpawnToolButton, &mut BackgroundColor)>,
+    spawn_tool: Res<SpawnTool>,
+) {
+    for (interaction, button, mut color) in &mut button_query {
+        let new_color = if spawn_tool.selected == Some(button.entity_type) {
+            PRESSED_BUTTON
+        } else if *interaction == Interaction::Hovered {
+            HOVERED_BUTTON
+        } else {
+            NORMAL_BUTTON
+        };
+        color.set_if_neq(BackgroundColor(new_color));
+    }
+}
+
+// Pressing the mouse picks where the entity goes and releasing it spawns the entity.
+// Balls take their velocity from the drag and walls are stretched over the dragged rectangle,
+// the other archetypes ignore the drag.
+fn spawn_at_cursor(
+    mut commands: Commands,
+    mut spawn_tool: ResMut<SpawnTool>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&Camera, &GlobalTransform)>,
+    interaction_query: Query<&Interaction>,
+    config: Res<GameConfig>,
+    mut gizmos: Gizmos,
+) {
+    let Some(selected) = spawn_tool.selected else {
+        return;
+    };
+    let (camera, camera_transform) = *camera;
+    let Some(cursor) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok())
+    else {
+        return;
+    };
+
+    // Clicks on the buttons aren't meant for the arena
+    let over_ui = interaction_query.iter().any(|interaction| *interaction != Interaction::None);
+    if mouse_input.just_pressed(MouseButton::Left) && !over_ui {
+        spawn_tool.drag_start = Some(cursor);
+    }
+    let Some(start) = spawn_tool.drag_start else {
+        return;
+    };
+
+    match selected {
+        EntityType::Ball => gizmos.line_2d(start, cursor, DRAG_PREVIEW_COLOR),
+        EntityType::Wall => gizmos.rect_2d((start + cursor) / 2.0, wall_size(start, cursor, &config), DRAG_PREVIEW_COLOR),
+        _ => {}
+    }
+    if !mouse_input.just_released(MouseButton::Left) {
+        return;
+    }
+    spawn_tool.drag_start = None;
+    match selected {
+        EntityType::Ball => {
+            let velocity = (cursor - start) * DRAG_TO_VELOCITY;
+            spawn_ball_with_velocity(&mut commands, &config, start, velocity, color(config.ball.color));
+        }
+        EntityType::Paddle => {
+            spawn_paddle_at(&mut commands, &config, start);
+        }
+        EntityType::Brick => {
+            spawn_brick(&mut commands, &config, start);
+        }
+        EntityType::Wall => {
+            spawn_wall(&mut commands, &config, (start + cursor) / 2.0, wall_size(start, cursor, &config));
+        }
+        EntityType::Capsule | EntityType::Projectile | EntityType::Score => {}
+    }
+}
 
-    // Update the paddle position,
-    // making sure it doesn't cause the paddle to leave the arena
-    let left_bound = LEFT_WALL + WALL_THICKNESS / 2.0 + PADDLE_SIZE.x / 2.0 + PADDLE_PADDING;
-    let right_bound = RIGHT_WALL - WALL_THICKNESS / 2.0 - PADDLE_SIZE.x / 2.0 - PADDLE_PADDING;
+// A click without a drag still gives a wall as thick as the arena's
+fn wall_size(start: Vec2, end: Vec2, config: &GameConfig) -> Vec2 {
+    (end - start).abs().max(Vec2::splat(config.arena.wall_thickness))
+}
 
-    entity_transform.translation.x = new_entity_position.clamp(left_bound, right_bound);
+fn is_on(entities: &[EntityRef], component: StashableComponent) -> bool {
+    !entities.is_empty() && entities.iter().all(|entity| component.is_present(*entity))
 }
 
 /* 
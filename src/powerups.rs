@@ -0,0 +1,535 @@
+//! Power-up capsules that destroyed bricks drop, and the effects of catching them.
+//!
+//! Each destroyed brick has a `PowerUpConfig::drop_chance` of dropping a `Capsule`, which falls
+//! with a `Velocity` and is caught by touching anything `Controllable`. Catching one starts its
+//! `PowerUp`:
+//! - wide paddle scales every paddle's width,
+//! - multiball splits every ball in play into several,
+//! - slow ball scales every ball's speed, and restores it once it runs out,
+//! - sticky paddle holds balls that land on a paddle until `LAUNCH_KEY` is pressed,
+//! - safety net spawns a wall just above the bottom of the arena,
+//! - laser lets paddles fire at the bricks, see `lasers`.
+//!
+//! Multiball happens once, when it's caught. The others last `PowerUpConfig::duration` seconds.
+//! Catching one that's already active starts its timer over instead of stacking on top of it, so
+//! two wide paddles are as wide as one, and different power-ups run side by side. Balls and paddles
+//! spawned while a power-up is active get it too. The active power-ups and their time left are
+//! shown in the top right corner.
+use bevy::{prelude::*, utils::HashMap, utils::HashSet};
+use rand::{seq::SliceRandom, Rng};
+use serde::Deserialize;
+
+use crate::{
+    config::{color, GameConfig},
+    insert_components, spawn_ball_with_velocity, spawn_wall,
+    stash::StashCommandsExt,
+    states::InGame,
+    CollisionEvent, Collision, Controllable, DestroyedEvent, EntityType, Velocity,
+};
+
+/// Lets go of balls held by a sticky paddle.
+pub const LAUNCH_KEY: KeyCode = KeyCode::Space;
+
+// Capsules fall in front of the bricks they drop from
+const CAPSULE_Z: f32 = 0.5;
+// Degrees between the balls multiball splits one ball into
+const MULTIBALL_SPREAD: f32 = 15.0;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerUp {
+    WidePaddle,
+    Multiball,
+    SlowBall,
+    Sticky,
+    SafetyNet,
+    Laser,
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 6] = [
+        PowerUp::WidePaddle,
+        PowerUp::Multiball,
+        PowerUp::SlowBall,
+        PowerUp::Sticky,
+        PowerUp::SafetyNet,
+        PowerUp::Laser,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUp::WidePaddle => "Wide paddle",
+            PowerUp::Multiball => "Multiball",
+            PowerUp::SlowBall => "Slow ball",
+            PowerUp::Sticky => "Sticky paddle",
+            PowerUp::SafetyNet => "Safety net",
+            PowerUp::Laser => "Laser",
+        }
+    }
+
+    fn color(self) -> Color {
+        let rgb = match self {
+            PowerUp::WidePaddle => [0.3, 0.3, 0.7],
+            PowerUp::Multiball => [1.0, 0.5, 0.5],
+            PowerUp::SlowBall => [0.4, 0.7, 1.0],
+            PowerUp::Sticky => [0.9, 0.8, 0.2],
+            PowerUp::SafetyNet => [0.4, 0.8, 0.4],
+            PowerUp::Laser => [0.9, 0.2, 0.2],
+        };
+        color(rgb)
+    }
+}
+
+/// A falling power-up, waiting to be caught.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Capsule(pub PowerUp);
+
+/// The timed power-ups that are running, and how long each has left.
+#[derive(Resource, Debug, Default)]
+pub struct ActivePowerUps {
+    timers: HashMap<PowerUp, Timer>,
+}
+
+impl ActivePowerUps {
+    /// Starts `power_up`, or starts its timer over if it's already running.
+    pub fn activate(&mut self, power_up: PowerUp, duration: f32) {
+        self.timers
+            .insert(power_up, Timer::from_seconds(duration, TimerMode::Once));
+    }
+
+    pub fn is_active(&self, power_up: PowerUp) -> bool {
+        self.timers.contains_key(&power_up)
+    }
+
+    /// Seconds until `power_up` runs out, if it's running.
+    pub fn remaining_secs(&self, power_up: PowerUp) -> Option<f32> {
+        self.timers.get(&power_up).map(Timer::remaining_secs)
+    }
+
+    pub fn clear(&mut self) {
+        self.timers.clear();
+    }
+}
+
+/// A ball slowed by slow ball, holding the speed it goes back to once slow ball runs out.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Slowed(pub f32);
+
+/// Marks the wall spawned by safety net.
+#[derive(Component, Debug)]
+pub struct SafetyNet;
+
+/// A ball held by a sticky paddle. Its `Velocity` is stashed while it's held.
+#[derive(Component, Debug)]
+pub struct StuckTo {
+    pub paddle: Entity,
+    pub offset: Vec2,
+}
+
+#[derive(Component)]
+pub(crate) struct PowerUpHud;
+
+/// Spawns a capsule holding `power_up`, falling at `PowerUpConfig::fall_speed`.
+pub fn spawn_capsule(commands: &mut Commands, config: &GameConfig, position: Vec2, power_up: PowerUp) -> Entity {
+    let power_ups = &config.power_ups;
+    let mut capsule_entity = commands.spawn((
+        Sprite {
+            color: power_up.color(),
+            ..default()
+        },
+        Transform {
+            translation: position.extend(CAPSULE_Z),
+            scale: Vec2::from(power_ups.capsule_size).extend(1.0),
+            ..default()
+        },
+        EntityType::Capsule,
+        Capsule(power_up),
+        config.collision_layers(EntityType::Capsule).unwrap_or_default(),
+        StateScoped(InGame),
+    ));
+    insert_components(&mut capsule_entity, &power_ups.components, Vec2::new(0.0, -power_ups.fall_speed));
+    capsule_entity.id()
+}
+
+pub(crate) fn spawn_hud(commands: &mut Commands, config: &GameConfig) {
+    let scoreboard = &config.scoreboard;
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: scoreboard.font_size,
+            ..default()
+        },
+        TextColor(color(scoreboard.text_color)),
+        PowerUpHud,
+        StateScoped(InGame),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(scoreboard.padding),
+            right: Val::Px(scoreboard.padding),
+            ..default()
+        },
+    ));
+}
+
+pub(crate) fn drop_capsules(
+    mut commands: Commands,
+    mut destroyed_events: EventReader<DestroyedEvent>,
+    transform_query: Query<&Transform>,
+    config: Res<GameConfig>,
+) {
+    let mut rng = rand::thread_rng();
+    for event in destroyed_events.read() {
+        if !rng.gen_bool(config.power_ups.drop_chance.into()) {
+            continue;
+        }
+        let (Ok(transform), Some(&power_up)) = (
+            transform_query.get(event.destroyed),
+            config.power_ups.drops.choose(&mut rng),
+        ) else {
+            continue;
+        };
+        spawn_capsule(&mut commands, &config, transform.translation.truncate(), power_up);
+    }
+}
+
+pub(crate) fn catch_capsules(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    capsule_query: Query<&Capsule>,
+    controllable_query: Query<(), With<Controllable>>,
+    ball_query: Query<(&EntityType, &Transform, Option<&Velocity>, Option<&Slowed>)>,
+    mut active: ResMut<ActivePowerUps>,
+    config: Res<GameConfig>,
+) {
+    // A capsule touching two paddles at once is still only caught once
+    let mut caught = HashSet::new();
+    for event in collision_events.read().filter(|event| event.started()) {
+        for (entity, other) in [(event.e1, event.e2), (event.e2, event.e1)] {
+            let Ok(&Capsule(power_up)) = capsule_query.get(entity) else {
+                continue;
+            };
+            if !controllable_query.contains(other) || !caught.insert(entity) {
+                continue;
+            }
+            commands.entity(entity).despawn_recursive();
+            match power_up {
+                PowerUp::Multiball => split_balls(&mut commands, &config, &ball_query),
+                power_up => active.activate(power_up, config.power_ups.duration),
+            }
+        }
+    }
+}
+
+// Every moving ball gets company heading off at slightly different angles
+fn split_balls(
+    commands: &mut Commands,
+    config: &GameConfig,
+    ball_query: &Query<(&EntityType, &Transform, Option<&Velocity>, Option<&Slowed>)>,
+) {
+    let balls: Vec<_> = ball_query
+        .iter()
+        .filter(|(entity_type, ..)| **entity_type == EntityType::Ball)
+        .collect();
+    let mut room = config.power_ups.max_balls.saturating_sub(balls.len());
+    for (_, transform, velocity, slowed) in balls {
+        let Some(velocity) = velocity else {
+            continue;
+        };
+        for index in 0..config.power_ups.multiball_extra_balls.min(room) {
+            // Alternates sides, fanning further out with each pair
+            let side = if index % 2 == 0 { 1.0 } else { -1.0 };
+            let angle = side * MULTIBALL_SPREAD.to_radians() * (index / 2 + 1) as f32;
+            let new_velocity = Vec2::from_angle(angle).rotate(velocity.0);
+            // Each copy starts a diameter further out than the last along its own heading, so none of
+            // them overlap and they don't bounce off each other straight away
+            let diameter = transform.scale.x;
+            let offset = new_velocity.normalize_or_zero() * diameter * (index + 1) as f32;
+            let position = inside_walls(config, transform.translation.truncate() + offset, diameter / 2.0);
+            let ball = spawn_ball_with_velocity(commands, config, position, new_velocity, color(config.ball.color));
+            // Copies of a slowed ball are already slow
+            if let Some(&slowed) = slowed {
+                commands.entity(ball).insert(slowed);
+            }
+            room -= 1;
+        }
+    }
+}
+
+// Keeps a ball of `radius` at `position` from starting in or past a wall
+fn inside_walls(config: &GameConfig, position: Vec2, radius: f32) -> Vec2 {
+    let arena = &config.arena;
+    let margin = arena.wall_thickness / 2.0 + radius;
+    let min = Vec2::new(arena.left_wall, arena.bottom_wall) + margin;
+    let max = Vec2::new(arena.right_wall, arena.top_wall) - margin;
+    position.clamp(min, max.max(min))
+}
+
+pub(crate) fn expire_power_ups(mut active: ResMut<ActivePowerUps>, time: Res<Time>) {
+    active.timers.retain(|_, timer| !timer.tick(time.delta()).finished());
+}
+
+// Brings balls and paddles in line with the active power-ups, including ones spawned since they started
+pub(crate) fn apply_power_ups(
+    mut commands: Commands,
+    active: Res<ActivePowerUps>,
+    config: Res<GameConfig>,
+    mut ball_query: Query<(Entity, &EntityType, &mut Velocity, Option<&Slowed>)>,
+    mut paddle_query: Query<(&EntityType, &mut Transform)>,
+    safety_net_query: Query<Entity, With<SafetyNet>>,
+) {
+    let power_ups = &config.power_ups;
+    let slow = active.is_active(PowerUp::SlowBall);
+    for (entity, entity_type, mut velocity, slowed) in &mut ball_query {
+        if *entity_type != EntityType::Ball {
+            continue;
+        }
+        match (slow, slowed) {
+            (true, None) => {
+                commands.entity(entity).insert(Slowed(velocity.length()));
+                velocity.0 *= power_ups.slow_ball_scale;
+            }
+            (false, Some(&Slowed(speed))) => {
+                velocity.0 = velocity.normalize_or_zero() * speed;
+                commands.entity(entity).remove::<Slowed>();
+            }
+            _ => {}
+        }
+    }
+
+    let paddle_width = if active.is_active(PowerUp::WidePaddle) {
+        config.paddle.size[0] * power_ups.wide_paddle_scale
+    } else {
+        config.paddle.size[0]
+    };
+    for (entity_type, mut transform) in &mut paddle_query {
+        if *entity_type == EntityType::Paddle && transform.scale.x != paddle_width {
+            transform.scale.x = paddle_width;
+        }
+    }
+
+    let net = safety_net_query.get_single().ok();
+    match (active.is_active(PowerUp::SafetyNet), net) {
+        (true, None) => {
+            let arena = &config.arena;
+            let position = Vec2::new(
+                (arena.left_wall + arena.right_wall) / 2.0,
+                arena.bottom_wall + arena.wall_thickness,
+            );
+            let size = Vec2::new(arena.right_wall - arena.left_wall, arena.wall_thickness);
+            let net = spawn_wall(&mut commands, &config, position, size);
+            commands.entity(net).insert(SafetyNet);
+        }
+        (false, Some(net)) => commands.entity(net).despawn_recursive(),
+        _ => {}
+    }
+}
+
+// Balls that land on top of a paddle stay there while the paddle is sticky
+pub(crate) fn stick_balls(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    active: Res<ActivePowerUps>,
+    entity_query: Query<(&EntityType, &Transform), Without<StuckTo>>,
+) {
+    let sticky = active.is_active(PowerUp::Sticky);
+    for event in collision_events.read().filter(|event| sticky && event.started()) {
+        let sides = [
+            (event.e1, event.e2, event.hit_side_of_e1),
+            (event.e2, event.e1, event.hit_side_of_e1.opposite()),
+        ];
+        for (ball, paddle, side) in sides {
+            let (Ok((EntityType::Ball, ball_transform)), Ok((EntityType::Paddle, paddle_transform))) =
+                (entity_query.get(ball), entity_query.get(paddle))
+            else {
+                continue;
+            };
+            if side != Collision::Top {
+                continue;
+            }
+            let offset = (ball_transform.translation - paddle_transform.translation).truncate();
+            commands.entity(ball).stash::<Velocity>().insert(StuckTo { paddle, offset });
+        }
+    }
+}
+
+// Held balls ride along with their paddle until they're launched or the paddle stops being sticky
+pub(crate) fn carry_stuck_balls(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    active: Res<ActivePowerUps>,
+    mut stuck_query: Query<(Entity, &StuckTo, &mut Transform)>,
+    paddle_query: Query<&Transform, Without<StuckTo>>,
+) {
+    let launch = keyboard_input.pressed(LAUNCH_KEY) || !active.is_active(PowerUp::Sticky);
+    for (entity, stuck, mut transform) in &mut stuck_query {
+        let paddle = paddle_query.get(stuck.paddle);
+        if let Ok(paddle) = paddle {
+            transform.translation = (paddle.translation.truncate() + stuck.offset).extend(transform.translation.z);
+        }
+        if launch || paddle.is_err() {
+            commands.entity(entity).remove::<StuckTo>().restore::<Velocity>();
+        }
+    }
+}
+
+// Capsules only collide with what catches them, so missed ones fall out of the arena
+pub(crate) fn despawn_missed_capsules(
+    mut commands: Commands,
+    capsule_query: Query<(Entity, &Transform), With<Capsule>>,
+    config: Res<GameConfig>,
+) {
+    let floor = config.arena.bottom_wall - config.arena.wall_thickness;
+    for (entity, transform) in &capsule_query {
+        if transform.translation.y < floor {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub(crate) fn update_hud(active: Res<ActivePowerUps>, mut hud_query: Query<&mut Text, With<PowerUpHud>>) {
+    let lines: Vec<String> = PowerUp::ALL
+        .into_iter()
+        .filter_map(|power_up| {
+            let remaining = active.remaining_secs(power_up)?;
+            Some(format!("{} {:.0}s", power_up.name(), remaining.ceil()))
+        })
+        .collect();
+    let hud = lines.join("\n");
+    for mut text in &mut hud_query {
+        if text.0 != hud {
+            text.0 = hud.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{CollisionPhase, Manifold};
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.insert_resource(GameConfig::default());
+        world.init_resource::<ActivePowerUps>();
+        world.init_resource::<Time>();
+        world.init_resource::<Events<CollisionEvent>>();
+        world
+    }
+
+    fn touch(world: &mut World, e1: Entity, e2: Entity, hit_side_of_e1: Collision) {
+        world.send_event(CollisionEvent {
+            e1,
+            e2,
+            hit_side_of_e1,
+            manifold: Manifold { normal: Vec2::Y, depth: 1.0, point: Vec2::ZERO },
+            phase: CollisionPhase::Started,
+        });
+    }
+
+    fn expire(world: &mut World, secs: f32) {
+        world.resource_mut::<Time>().advance_by(Duration::from_secs_f32(secs));
+        world.run_system_once(expire_power_ups).unwrap();
+        world.run_system_once(apply_power_ups).unwrap();
+    }
+
+    fn balls(world: &mut World) -> usize {
+        let mut query = world.query::<&EntityType>();
+        query.iter(world).filter(|&&entity_type| entity_type == EntityType::Ball).count()
+    }
+
+    #[test]
+    fn catching_again_restarts_the_timer() {
+        let mut world = world();
+        let duration = world.resource::<GameConfig>().power_ups.duration;
+        let paddle = world.spawn(Controllable).id();
+        for _ in 0..2 {
+            let capsule = world.spawn(Capsule(PowerUp::WidePaddle)).id();
+            touch(&mut world, capsule, paddle, Collision::Bottom);
+            world.run_system_once(catch_capsules).unwrap();
+            assert!(world.get_entity(capsule).is_err());
+            expire(&mut world, duration * 0.75);
+        }
+
+        let active = world.resource::<ActivePowerUps>();
+        assert!(active.is_active(PowerUp::WidePaddle));
+        assert!((active.remaining_secs(PowerUp::WidePaddle).unwrap() - duration * 0.25).abs() < 1e-3);
+    }
+
+    #[test]
+    fn slow_ball_is_undone_when_it_runs_out() {
+        let mut world = world();
+        let (duration, scale) = {
+            let power_ups = &world.resource::<GameConfig>().power_ups;
+            (power_ups.duration, power_ups.slow_ball_scale)
+        };
+        let ball = world.spawn((EntityType::Ball, Velocity(Vec2::new(300.0, 400.0)))).id();
+        world.resource_mut::<ActivePowerUps>().activate(PowerUp::SlowBall, duration);
+
+        world.run_system_once(apply_power_ups).unwrap();
+        assert!((world.get::<Velocity>(ball).unwrap().length() - 500.0 * scale).abs() < 1e-3);
+        assert!(world.entity(ball).contains::<Slowed>());
+
+        expire(&mut world, duration);
+        assert!(world.get::<Velocity>(ball).unwrap().abs_diff_eq(Vec2::new(300.0, 400.0), 1e-3));
+        assert!(!world.entity(ball).contains::<Slowed>());
+    }
+
+    #[test]
+    fn multiball_stops_at_max_balls() {
+        let mut world = world();
+        let max_balls = world.resource::<GameConfig>().power_ups.max_balls;
+        let paddle = world.spawn(Controllable).id();
+        for _ in 0..max_balls - 1 {
+            world.spawn((EntityType::Ball, Transform::default(), Velocity(Vec2::new(0.0, 300.0))));
+        }
+        let capsule = world.spawn(Capsule(PowerUp::Multiball)).id();
+
+        touch(&mut world, paddle, capsule, Collision::Top);
+        world.run_system_once(catch_capsules).unwrap();
+
+        assert_eq!(balls(&mut world), max_balls);
+        assert!(!world.resource::<ActivePowerUps>().is_active(PowerUp::Multiball));
+    }
+
+    #[test]
+    fn sticky_paddle_holds_balls_until_it_runs_out() {
+        let mut world = world();
+        world.init_resource::<ButtonInput<KeyCode>>();
+        let duration = world.resource::<GameConfig>().power_ups.duration;
+        world.resource_mut::<ActivePowerUps>().activate(PowerUp::Sticky, duration);
+        let paddle = world.spawn((EntityType::Paddle, Transform::from_xyz(0.0, -100.0, 0.0))).id();
+        let ball =
+            world.spawn((EntityType::Ball, Transform::from_xyz(20.0, -80.0, 1.0), Velocity(Vec2::new(100.0, -300.0)))).id();
+
+        touch(&mut world, ball, paddle, Collision::Top);
+        world.run_system_once(stick_balls).unwrap();
+        assert!(world.get::<Velocity>(ball).is_none());
+
+        world.get_mut::<Transform>(paddle).unwrap().translation.x = 50.0;
+        world.run_system_once(carry_stuck_balls).unwrap();
+        assert_eq!(world.get::<Transform>(ball).unwrap().translation, Vec3::new(70.0, -80.0, 1.0));
+
+        expire(&mut world, duration);
+        world.run_system_once(carry_stuck_balls).unwrap();
+        assert!(!world.entity(ball).contains::<StuckTo>());
+        assert_eq!(world.get::<Velocity>(ball).map(|velocity| velocity.0), Some(Vec2::new(100.0, -300.0)));
+    }
+
+    #[test]
+    fn safety_net_comes_and_goes() {
+        let mut world = world();
+        let duration = world.resource::<GameConfig>().power_ups.duration;
+        world.resource_mut::<ActivePowerUps>().activate(PowerUp::SafetyNet, duration);
+        let nets = |world: &mut World| world.query_filtered::<(), With<SafetyNet>>().iter(world).count();
+
+        world.run_system_once(apply_power_ups).unwrap();
+        world.run_system_once(apply_power_ups).unwrap();
+        assert_eq!(nets(&mut world), 1);
+
+        expire(&mut world, duration);
+        assert_eq!(nets(&mut world), 0);
+    }
+}
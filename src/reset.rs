@@ -0,0 +1,173 @@
+//! Rebuilds the arena in place, so a playtest can start over without restarting the app.
+//!
+//! Send a `ResetWorld` from code, or press R. Every gameplay entity is despawned, pending collision,
+//! destruction and bonus events are dropped along with the touching pairs and running power-ups,
+//! `Score` and `Lives` start over and the arena is spawned again the same way a new game spawns it,
+//! from the first level, or the level being play-tested.
+//! The game is then `Playing`, whichever state it was in.
+use bevy::prelude::*;
+
+use crate::{
+    collision::CollisionTracker,
+    config::{color, ConfigError, GameConfig},
+    editor::{starting_level, PlayTest},
+    levels::CurrentLevel,
+    lives::Lives,
+    powerups::ActivePowerUps,
+    scoring::ScoreBonus,
+    spawn_entities,
+    states::{GameState, InGame},
+    CollisionEvent, DestroyedEvent, Score,
+};
+
+const RESET_KEY: KeyCode = KeyCode::KeyR;
+
+/// Resets the world, handled in `Update`.
+#[derive(Event, Debug, Default)]
+pub struct ResetWorld {
+    /// Replaces `GameConfig` before respawning, otherwise the current config is kept.
+    /// An invalid config is reported and the current one is kept as well.
+    pub config: Option<GameConfig>,
+}
+
+impl ResetWorld {
+    pub fn with_config(config: GameConfig) -> Self {
+        ResetWorld { config: Some(config) }
+    }
+}
+
+pub(crate) fn reset_on_key(keyboard_input: Res<ButtonInput<KeyCode>>, mut resets: EventWriter<ResetWorld>) {
+    if keyboard_input.just_pressed(RESET_KEY) {
+        resets.send_default();
+    }
+}
+
+pub(crate) fn reset_world(
+    mut commands: Commands,
+    mut resets: EventReader<ResetWorld>,
+    mut config: ResMut<GameConfig>,
+    mut clear_color: ResMut<ClearColor>,
+    mut score: ResMut<Score>,
+    mut lives: ResMut<Lives>,
+    mut collision_events: ResMut<Events<CollisionEvent>>,
+    mut collision_tracker: ResMut<CollisionTracker>,
+    mut destroyed_events: ResMut<Events<DestroyedEvent>>,
+    mut bonuses: ResMut<Events<ScoreBonus>>,
+    mut power_ups: ResMut<ActivePowerUps>,
+    mut current_level: ResMut<CurrentLevel>,
+    play_test: Res<PlayTest>,
+    gameplay_query: Query<Entity, With<StateScoped<InGame>>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    // Several resets in one frame only need to happen once, with the newest config
+    let mut reset = false;
+    for event in resets.read() {
+        reset = true;
+        let Some(new_config) = &event.config else {
+            continue;
+        };
+        match new_config.validate() {
+            Ok(()) => *config = new_config.clone(),
+            Err(problems) => {
+                error!("Resetting with {}\nKeeping the current config.", ConfigError::Invalid(problems));
+            }
+        }
+    }
+    if !reset {
+        return;
+    }
+    clear_color.0 = color(config.arena.background_color);
+    next_state.set(GameState::Playing);
+    // Leaving the menu or the editor starts a game, which spawns the arena on its own
+    if matches!(state.get(), GameState::MainMenu | GameState::Editor) {
+        return;
+    }
+
+    for entity in &gameplay_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    collision_events.clear();
+    collision_tracker.clear();
+    destroyed_events.clear();
+    bonuses.clear();
+    power_ups.clear();
+    **score = 0;
+    **lives = config.lives.starting;
+    **current_level = 0;
+    spawn_entities(&mut commands, &config, starting_level(&config, &play_test).as_ref());
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::EntityType;
+
+    fn world(state: GameState) -> World {
+        let mut world = World::new();
+        world.insert_resource(GameConfig::default());
+        world.insert_resource(ClearColor::default());
+        world.insert_resource(Score(120));
+        world.insert_resource(Lives(1));
+        world.insert_resource(State::new(state));
+        world.init_resource::<NextState<GameState>>();
+        world.init_resource::<CollisionTracker>();
+        world.init_resource::<ActivePowerUps>();
+        world.insert_resource(CurrentLevel(2));
+        world.init_resource::<PlayTest>();
+        world.init_resource::<Events<ResetWorld>>();
+        world.init_resource::<Events<CollisionEvent>>();
+        world.init_resource::<Events<DestroyedEvent>>();
+        world.init_resource::<Events<ScoreBonus>>();
+        world
+    }
+
+    fn count(world: &mut World, entity_type: EntityType) -> usize {
+        let mut query = world.query::<&EntityType>();
+        query.iter(world).filter(|&&found| found == entity_type).count()
+    }
+
+    #[test]
+    fn reset_respawns_the_arena_and_starts_over() {
+        let mut world = world(GameState::GameOver);
+        let leftover = world.spawn((EntityType::Ball, StateScoped(InGame))).id();
+        let kept = world.spawn(EntityType::Ball).id();
+        world.send_event(ResetWorld::default());
+        world.run_system_once(reset_world).unwrap();
+
+        assert!(world.get_entity(leftover).is_err());
+        assert!(world.get_entity(kept).is_ok());
+        assert_eq!(count(&mut world, EntityType::Paddle), 1);
+        assert_eq!(count(&mut world, EntityType::Ball), 1 + world.resource::<GameConfig>().ball.spawns.len());
+        assert_eq!(world.resource::<Score>().0, 0);
+        assert_eq!(world.resource::<Lives>().0, world.resource::<GameConfig>().lives.starting);
+        assert_eq!(world.resource::<CurrentLevel>().0, 0);
+        assert!(matches!(*world.resource::<NextState<GameState>>(), NextState::Pending(GameState::Playing)));
+    }
+
+    #[test]
+    fn newest_config_wins() {
+        let mut world = world(GameState::Playing);
+        for starting in [5, 7] {
+            let mut config = GameConfig::default();
+            config.lives.starting = starting;
+            world.send_event(ResetWorld::with_config(config));
+        }
+        world.run_system_once(reset_world).unwrap();
+
+        assert_eq!(world.resource::<GameConfig>().lives.starting, 7);
+        assert_eq!(world.resource::<Lives>().0, 7);
+    }
+
+    #[test]
+    fn reset_from_the_menu_only_starts_the_game() {
+        let mut world = world(GameState::MainMenu);
+        world.send_event(ResetWorld::default());
+        world.run_system_once(reset_world).unwrap();
+
+        assert_eq!(count(&mut world, EntityType::Paddle), 0);
+        assert!(matches!(*world.resource::<NextState<GameState>>(), NextState::Pending(GameState::Playing)));
+    }
+}
@@ -0,0 +1,166 @@
+//! Awards points when a `Destructor` destroys a `Destructable`.
+//!
+//! Each destroyed entity is worth its `Points`, multiplied by the destructor's `Combo`. The combo
+//! grows with every entity a destructor destroys and resets when it touches a paddle, so a ball
+//! that clears several bricks between paddle hits is worth more. Other systems can award
+//! flat bonuses by sending a `ScoreBonus`. The running total is the `Score` resource.
+use bevy::prelude::*;
+
+use crate::{config::GameConfig, CollisionEvent, DestroyedEvent, EntityType, Score};
+
+/// How many points destroying this entity is worth, before the combo multiplier.
+/// Entities without it are worth nothing.
+#[derive(Component, Debug, Clone, Copy, Deref, DerefMut)]
+pub struct Points(pub usize);
+
+/// How many entities a `Destructor` has destroyed since it last touched a paddle.
+#[derive(Component, Debug, Default)]
+pub struct Combo {
+    pub hits: usize,
+}
+
+impl Combo {
+    /// The first hit counts once, the second twice, and so on up to `max`.
+    pub fn multiplier(&self, max: usize) -> usize {
+        (self.hits + 1).min(max)
+    }
+}
+
+/// Adds `points` to the score as they are, without a multiplier.
+#[derive(Event, Debug)]
+pub struct ScoreBonus {
+    pub points: usize,
+}
+
+pub(crate) fn award_points(
+    mut destroyed_events: EventReader<DestroyedEvent>,
+    mut score: ResMut<Score>,
+    points_query: Query<&Points>,
+    mut combo_query: Query<&mut Combo>,
+    config: Res<GameConfig>,
+) {
+    for event in destroyed_events.read() {
+        let points = points_query.get(event.destroyed).map_or(0, |points| points.0);
+        let multiplier = match combo_query.get_mut(event.destructor) {
+            Ok(mut combo) => {
+                let multiplier = combo.multiplier(config.scoring.max_combo_multiplier);
+                combo.hits += 1;
+                multiplier
+            }
+            Err(_) => 1,
+        };
+        **score += points * multiplier;
+    }
+}
+
+pub(crate) fn award_bonuses(mut bonuses: EventReader<ScoreBonus>, mut score: ResMut<Score>) {
+    for bonus in bonuses.read() {
+        **score += bonus.points;
+    }
+}
+
+pub(crate) fn reset_combos(
+    mut collision_events: EventReader<CollisionEvent>,
+    entity_type_query: Query<&EntityType>,
+    mut combo_query: Query<&mut Combo>,
+) {
+    for event in collision_events.read().filter(|event| event.started()) {
+        for (entity, other) in [(event.e1, event.e2), (event.e2, event.e1)] {
+            // Balls are `Controllable` too, so another ball doesn't count
+            if entity_type_query.get(other) != Ok(&EntityType::Paddle) {
+                continue;
+            }
+            if let Ok(mut combo) = combo_query.get_mut(entity) {
+                combo.hits = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{Collision, CollisionPhase, Controllable, Manifold};
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.insert_resource(GameConfig::default());
+        world.insert_resource(Score(0));
+        world.init_resource::<Events<DestroyedEvent>>();
+        world.init_resource::<Events<ScoreBonus>>();
+        world.init_resource::<Events<CollisionEvent>>();
+        world
+    }
+
+    /// Destroys one entity per entry in `points`, all in the same tick.
+    fn destroy(world: &mut World, destructor: Entity, points: &[usize]) {
+        for &points in points {
+            let destroyed = world.spawn(Points(points)).id();
+            world.send_event(DestroyedEvent { destructor, destroyed });
+        }
+        world.run_system_once(award_points).unwrap();
+    }
+
+    fn touch(world: &mut World, e1: Entity, e2: Entity) {
+        world.send_event(CollisionEvent {
+            e1,
+            e2,
+            hit_side_of_e1: Collision::Bottom,
+            manifold: Manifold { normal: Vec2::NEG_Y, depth: 1.0, point: Vec2::ZERO },
+            phase: CollisionPhase::Started,
+        });
+        world.run_system_once(reset_combos).unwrap();
+    }
+
+    #[test]
+    fn combo_multiplies_points() {
+        let mut world = world();
+        let ball = world.spawn(Combo::default()).id();
+
+        destroy(&mut world, ball, &[10, 10, 10]);
+
+        assert_eq!(world.resource::<Score>().0, 10 + 20 + 30);
+        assert_eq!(world.get::<Combo>(ball).map(|combo| combo.hits), Some(3));
+    }
+
+    #[test]
+    fn combo_is_capped() {
+        let mut world = world();
+        let max = world.resource::<GameConfig>().scoring.max_combo_multiplier;
+        let ball = world.spawn(Combo { hits: max + 5 }).id();
+
+        destroy(&mut world, ball, &[10]);
+
+        assert_eq!(world.resource::<Score>().0, 10 * max);
+    }
+
+    #[test]
+    fn touching_a_paddle_resets_the_combo() {
+        let mut world = world();
+        let ball = world.spawn((EntityType::Ball, Controllable, Combo { hits: 4 })).id();
+        let brick = world.spawn(EntityType::Brick).id();
+        touch(&mut world, ball, brick);
+        assert_eq!(world.get::<Combo>(ball).map(|combo| combo.hits), Some(4));
+
+        let other_ball = world.spawn((EntityType::Ball, Controllable)).id();
+        touch(&mut world, other_ball, ball);
+        assert_eq!(world.get::<Combo>(ball).map(|combo| combo.hits), Some(4));
+
+        let paddle = world.spawn((EntityType::Paddle, Controllable)).id();
+        touch(&mut world, paddle, ball);
+        assert_eq!(world.get::<Combo>(ball).map(|combo| combo.hits), Some(0));
+    }
+
+    #[test]
+    fn bonuses_are_added_without_a_multiplier() {
+        let mut world = world();
+        world.resource_mut::<Score>().0 = 5;
+
+        world.send_event(ScoreBonus { points: 100 });
+        world.run_system_once(award_bonuses).unwrap();
+
+        assert_eq!(world.resource::<Score>().0, 105);
+    }
+}
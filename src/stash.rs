@@ -0,0 +1,323 @@
+//! Stash-and-restore storage for toggled components.
+//!
+//! Removing a `Velocity` freezes a ball, but the velocity is lost. Stashing it instead moves the
+//! component into the entity's `Storage`, and restoring it puts that same value back, so the ball
+//! keeps going in its old direction.
+//!
+//! `Storage` lives on the entity itself, so despawning the entity drops whatever it had stashed.
+use bevy::{ecs::system::EntityCommands, ecs::world::EntityWorldMut, prelude::*};
+use serde::Deserialize;
+
+use crate::{Collider, Controllable, Destructable, Destructor, Velocity};
+
+/// The components that have been stashed off of an entity.
+/// Removed again once the last stashed component is restored.
+#[derive(Component, Default)]
+pub struct Storage {
+    velocity: Option<Velocity>,
+    collider: Option<Collider>,
+    controllable: Option<Controllable>,
+    destructor: Option<Destructor>,
+    destructable: Option<Destructable>,
+}
+
+impl Storage {
+    /// The stashed value of `T`, if there is one.
+    pub fn get<T: Stashable>(&self) -> Option<&T> {
+        T::slot(self).as_ref()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.velocity.is_none()
+            && self.collider.is_none()
+            && self.controllable.is_none()
+            && self.destructor.is_none()
+            && self.destructable.is_none()
+    }
+}
+
+/// A component that can be moved in and out of `Storage`.
+/// `Default` is used when one is switched on for an entity that never had it.
+pub trait Stashable: Component + Default {
+    fn slot(storage: &Storage) -> &Option<Self>;
+    fn slot_mut(storage: &mut Storage) -> &mut Option<Self>;
+}
+
+macro_rules! impl_stashable {
+    ($component:ty, $field:ident) => {
+        impl Stashable for $component {
+            fn slot(storage: &Storage) -> &Option<Self> {
+                &storage.$field
+            }
+
+            fn slot_mut(storage: &mut Storage) -> &mut Option<Self> {
+                &mut storage.$field
+            }
+        }
+    };
+}
+
+impl_stashable!(Velocity, velocity);
+impl_stashable!(Collider, collider);
+impl_stashable!(Controllable, controllable);
+impl_stashable!(Destructor, destructor);
+impl_stashable!(Destructable, destructable);
+
+/// Moves `T` off of the entity and into its `Storage`.
+/// Stashing again replaces the older stashed value, since the live one is more recent.
+/// Returns `false` if the entity had no `T` to stash.
+pub fn stash<T: Stashable>(entity: &mut EntityWorldMut) -> bool {
+    let Some(component) = entity.take::<T>() else {
+        return false;
+    };
+    match entity.get_mut::<Storage>() {
+        Some(mut storage) => *T::slot_mut(&mut storage) = Some(component),
+        None => {
+            let mut storage = Storage::default();
+            *T::slot_mut(&mut storage) = Some(component);
+            entity.insert(storage);
+        }
+    }
+    true
+}
+
+/// Moves the stashed `T` back onto the entity.
+/// If a `T` was added in the meantime the live one wins and the stashed value is dropped.
+/// Returns `false` if nothing was stashed.
+pub fn restore<T: Stashable>(entity: &mut EntityWorldMut) -> bool {
+    let Some(mut storage) = entity.get_mut::<Storage>() else {
+        return false;
+    };
+    let Some(component) = T::slot_mut(&mut storage).take() else {
+        return false;
+    };
+    if storage.is_empty() {
+        entity.remove::<Storage>();
+    }
+    if !entity.contains::<T>() {
+        entity.insert(component);
+    }
+    true
+}
+
+/// Restores the stashed `T`, or adds a default one if nothing was stashed.
+pub fn restore_or_default<T: Stashable>(entity: &mut EntityWorldMut) {
+    if !restore::<T>(entity) && !entity.contains::<T>() {
+        entity.insert(T::default());
+    }
+}
+
+/// Stashes `T` if the entity has it, otherwise restores it.
+/// Returns whether the entity has a `T` afterwards.
+pub fn toggle<T: Stashable>(entity: &mut EntityWorldMut) -> bool {
+    if entity.contains::<T>() {
+        stash::<T>(entity);
+    } else {
+        restore::<T>(entity);
+    }
+    entity.contains::<T>()
+}
+
+/// The stashable components by name, for code that picks them at runtime such as UI buttons and
+/// the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum StashableComponent {
+    Velocity,
+    Collider,
+    Controllable,
+    Destructor,
+    Destructable,
+}
+
+impl StashableComponent {
+    pub const ALL: [StashableComponent; 5] = [
+        StashableComponent::Velocity,
+        StashableComponent::Collider,
+        StashableComponent::Controllable,
+        StashableComponent::Destructor,
+        StashableComponent::Destructable,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StashableComponent::Velocity => "Velocity",
+            StashableComponent::Collider => "Collider",
+            StashableComponent::Controllable => "Controllable",
+            StashableComponent::Destructor => "Destructor",
+            StashableComponent::Destructable => "Destructable",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<StashableComponent> {
+        StashableComponent::ALL
+            .into_iter()
+            .find(|component| component.name() == name)
+    }
+
+    pub fn stash(self, entity: &mut EntityWorldMut) -> bool {
+        match self {
+            StashableComponent::Velocity => stash::<Velocity>(entity),
+            StashableComponent::Collider => stash::<Collider>(entity),
+            StashableComponent::Controllable => stash::<Controllable>(entity),
+            StashableComponent::Destructor => stash::<Destructor>(entity),
+            StashableComponent::Destructable => stash::<Destructable>(entity),
+        }
+    }
+
+    pub fn restore(self, entity: &mut EntityWorldMut) -> bool {
+        match self {
+            StashableComponent::Velocity => restore::<Velocity>(entity),
+            StashableComponent::Collider => restore::<Collider>(entity),
+            StashableComponent::Controllable => restore::<Controllable>(entity),
+            StashableComponent::Destructor => restore::<Destructor>(entity),
+            StashableComponent::Destructable => restore::<Destructable>(entity),
+        }
+    }
+
+    pub fn restore_or_default(self, entity: &mut EntityWorldMut) {
+        match self {
+            StashableComponent::Velocity => restore_or_default::<Velocity>(entity),
+            StashableComponent::Collider => restore_or_default::<Collider>(entity),
+            StashableComponent::Controllable => restore_or_default::<Controllable>(entity),
+            StashableComponent::Destructor => restore_or_default::<Destructor>(entity),
+            StashableComponent::Destructable => restore_or_default::<Destructable>(entity),
+        }
+    }
+
+    /// Whether the entity currently has this component, as opposed to having it stashed or never
+    /// having had it.
+    pub fn is_present(self, entity: EntityRef) -> bool {
+        match self {
+            StashableComponent::Velocity => entity.contains::<Velocity>(),
+            StashableComponent::Collider => entity.contains::<Collider>(),
+            StashableComponent::Controllable => entity.contains::<Controllable>(),
+            StashableComponent::Destructor => entity.contains::<Destructor>(),
+            StashableComponent::Destructable => entity.contains::<Destructable>(),
+        }
+    }
+
+    pub fn toggle(self, entity: &mut EntityWorldMut) -> bool {
+        match self {
+            StashableComponent::Velocity => toggle::<Velocity>(entity),
+            StashableComponent::Collider => toggle::<Collider>(entity),
+            StashableComponent::Controllable => toggle::<Controllable>(entity),
+            StashableComponent::Destructor => toggle::<Destructor>(entity),
+            StashableComponent::Destructable => toggle::<Destructable>(entity),
+        }
+    }
+}
+
+/// Queues stash operations from systems.
+/// Entities that are despawned before the command runs are skipped.
+pub trait StashCommandsExt {
+    fn stash<T: Stashable>(&mut self) -> &mut Self;
+    fn restore<T: Stashable>(&mut self) -> &mut Self;
+    fn toggle_stashed<T: Stashable>(&mut self) -> &mut Self;
+    fn stash_component(&mut self, component: StashableComponent) -> &mut Self;
+    fn restore_component(&mut self, component: StashableComponent) -> &mut Self;
+    fn restore_or_default_component(&mut self, component: StashableComponent) -> &mut Self;
+}
+
+fn on_live_entity(
+    f: impl FnOnce(&mut EntityWorldMut) + Send + 'static,
+) -> impl FnOnce(Entity, &mut World) + Send + 'static {
+    move |entity: Entity, world: &mut World| {
+        if let Ok(mut entity) = world.get_entity_mut(entity) {
+            f(&mut entity);
+        }
+    }
+}
+
+impl StashCommandsExt for EntityCommands<'_> {
+    fn stash<T: Stashable>(&mut self) -> &mut Self {
+        self.queue(on_live_entity(|entity| {
+            stash::<T>(entity);
+        }))
+    }
+
+    fn restore<T: Stashable>(&mut self) -> &mut Self {
+        self.queue(on_live_entity(|entity| {
+            restore::<T>(entity);
+        }))
+    }
+
+    fn toggle_stashed<T: Stashable>(&mut self) -> &mut Self {
+        self.queue(on_live_entity(|entity| {
+            toggle::<T>(entity);
+        }))
+    }
+
+    fn stash_component(&mut self, component: StashableComponent) -> &mut Self {
+        self.queue(on_live_entity(move |entity| {
+            component.stash(entity);
+        }))
+    }
+
+    fn restore_component(&mut self, component: StashableComponent) -> &mut Self {
+        self.queue(on_live_entity(move |entity| {
+            component.restore(entity);
+        }))
+    }
+
+    fn restore_or_default_component(&mut self, component: StashableComponent) -> &mut Self {
+        self.queue(on_live_entity(move |entity| {
+            component.restore_or_default(entity);
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_brings_back_the_stashed_value() {
+        let mut world = World::new();
+        let mut entity = world.spawn(Velocity(Vec2::new(3.0, -4.0)));
+
+        assert!(stash::<Velocity>(&mut entity));
+        assert!(!entity.contains::<Velocity>());
+        let stashed = entity.get::<Storage>().and_then(Storage::get::<Velocity>);
+        assert_eq!(stashed.map(|velocity| velocity.0), Some(Vec2::new(3.0, -4.0)));
+
+        assert!(restore::<Velocity>(&mut entity));
+        assert_eq!(entity.get::<Velocity>().map(|velocity| velocity.0), Some(Vec2::new(3.0, -4.0)));
+    }
+
+    #[test]
+    fn storage_is_removed_once_empty() {
+        let mut world = World::new();
+        let mut entity = world.spawn((Velocity(Vec2::X), Collider));
+        stash::<Velocity>(&mut entity);
+        stash::<Collider>(&mut entity);
+
+        restore::<Velocity>(&mut entity);
+        assert!(entity.contains::<Storage>());
+        restore::<Collider>(&mut entity);
+        assert!(!entity.contains::<Storage>());
+    }
+
+    #[test]
+    fn live_component_wins_over_the_stashed_one() {
+        let mut world = World::new();
+        let mut entity = world.spawn(Velocity(Vec2::X));
+        stash::<Velocity>(&mut entity);
+        entity.insert(Velocity(Vec2::Y));
+
+        assert!(restore::<Velocity>(&mut entity));
+        assert_eq!(entity.get::<Velocity>().map(|velocity| velocity.0), Some(Vec2::Y));
+        assert!(!entity.contains::<Storage>());
+    }
+
+    #[test]
+    fn commands_skip_despawned_entities() {
+        let mut world = World::new();
+        let entity = world.spawn(Velocity(Vec2::X)).id();
+        let mut commands = world.commands();
+        commands.entity(entity).despawn();
+        commands.entity(entity).stash::<Velocity>();
+        world.flush();
+
+        assert!(world.get_entity(entity).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! The game's states and the screens shown in each of them.
+//!
+//! ```text
+//! MainMenu --E--> Editor --Enter--> Playing, a play-test that goes back to the Editor on Enter
+//!    |               or once it's over, instead of the MainMenu
+//!    |
+//! MainMenu --Space--> Playing <--P--> Paused
+//!                      |   |
+//!                      |   +--no Destructables left--> LevelComplete --after a moment--> Playing
+//!                      |   +--no Destructables left on the last level--> Victory --Space--> MainMenu
+//!                      +--no lives left--> GameOver --Space--> MainMenu
+//! ```
+//!
+//! Gameplay entities are scoped to `InGame`, which covers every state but `MainMenu` and `Editor`, so pausing
+//! or finishing keeps the arena on screen and going back to the menu clears it. Each screen's UI is
+//! scoped to its own state.
+use bevy::prelude::*;
+
+use crate::{
+    config::{color, GameConfig},
+    editor::PlayTest,
+    levels::{is_last_level, CurrentLevel},
+    Destructable, Score,
+};
+
+const TITLE_FONT_SIZE_SCALE: f32 = 2.0;
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GameState {
+    #[default]
+    MainMenu,
+    /// The simulation only runs in this state.
+    Playing,
+    Paused,
+    /// Between levels, see `levels`.
+    LevelComplete,
+    /// Every level has been cleared.
+    Victory,
+    GameOver,
+    /// Editing a level, see `editor`.
+    Editor,
+}
+
+/// Active whenever there is a game in progress, i.e. outside of the main menu and the editor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InGame;
+
+impl ComputedStates for InGame {
+    type SourceStates = GameState;
+
+    fn compute(sources: GameState) -> Option<Self> {
+        match sources {
+            GameState::MainMenu | GameState::Editor => None,
+            _ => Some(InGame),
+        }
+    }
+}
+
+pub(crate) fn add_states(app: &mut App) {
+    app.init_state::<GameState>()
+        .add_computed_state::<InGame>()
+        .enable_state_scoped_entities::<GameState>()
+        .enable_state_scoped_entities::<InGame>()
+        .add_systems(OnEnter(GameState::MainMenu), spawn_main_menu)
+        .add_systems(OnEnter(GameState::Paused), (pause_time, spawn_pause_screen))
+        .add_systems(OnExit(GameState::Paused), unpause_time)
+        .add_systems(OnEnter(GameState::Victory), spawn_victory_screen)
+        .add_systems(OnEnter(GameState::GameOver), spawn_game_over_screen)
+        .add_systems(Update, handle_state_input);
+}
+
+fn handle_state_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    play_test: Res<PlayTest>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let next = match state.get() {
+        GameState::MainMenu if keyboard_input.just_pressed(KeyCode::Space) => GameState::Playing,
+        GameState::Playing if keyboard_input.just_pressed(KeyCode::KeyP) => GameState::Paused,
+        GameState::Paused if keyboard_input.just_pressed(KeyCode::KeyP) => GameState::Playing,
+        GameState::Victory | GameState::GameOver if keyboard_input.just_pressed(KeyCode::Space) => {
+            if play_test.0.is_some() {
+                GameState::Editor
+            } else {
+                GameState::MainMenu
+            }
+        }
+        _ => return,
+    };
+    next_state.set(next);
+}
+
+pub(crate) fn check_level_complete(
+    destructable_query: Query<(), With<Destructable>>,
+    current_level: Res<CurrentLevel>,
+    play_test: Res<PlayTest>,
+    mut next_state: ResMut<NextState<GameState>>,
+    config: Res<GameConfig>,
+) {
+    if !destructable_query.is_empty() {
+        return;
+    }
+    // A play-test is a single level
+    if play_test.0.is_some() || is_last_level(&config, **current_level) {
+        next_state.set(GameState::Victory);
+    } else {
+        next_state.set(GameState::LevelComplete);
+    }
+}
+
+// Freezing virtual time also stops anything else timed on it, like animations, while paused
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn unpause_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn spawn_main_menu(mut commands: Commands, config: Res<GameConfig>) {
+    spawn_screen(
+        &mut commands,
+        &config,
+        GameState::MainMenu,
+        "Breakout",
+        "Press Space to play, or E to edit levels",
+    );
+}
+
+fn spawn_pause_screen(mut commands: Commands, config: Res<GameConfig>) {
+    spawn_screen(&mut commands, &config, GameState::Paused, "Paused", "Press P to resume");
+}
+
+fn spawn_victory_screen(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    score: Res<Score>,
+    play_test: Res<PlayTest>,
+) {
+    spawn_screen(
+        &mut commands,
+        &config,
+        GameState::Victory,
+        "Victory",
+        &format!("Final score: {}\n{}", **score, return_hint(&play_test)),
+    );
+}
+
+fn spawn_game_over_screen(mut commands: Commands, config: Res<GameConfig>, play_test: Res<PlayTest>) {
+    spawn_screen(&mut commands, &config, GameState::GameOver, "Game Over", return_hint(&play_test));
+}
+
+fn return_hint(play_test: &PlayTest) -> &'static str {
+    if play_test.0.is_some() {
+        "Press Space to return to the editor"
+    } else {
+        "Press Space to return to the menu"
+    }
+}
+
+/// A centered title with a hint below it, removed when `state` ends.
+pub(crate) fn spawn_screen(commands: &mut Commands, config: &GameConfig, state: GameState, title: &str, hint: &str) {
+    let scoreboard = &config.scoreboard;
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            StateScoped(state),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(title),
+                TextFont {
+                    font_size: scoreboard.font_size * TITLE_FONT_SIZE_SCALE,
+                    ..default()
+                },
+                TextColor(color(scoreboard.text_color)),
+            ));
+            parent.spawn((
+                Text::new(hint),
+                TextFont {
+                    font_size: scoreboard.font_size,
+                    ..default()
+                },
+                TextColor(color(scoreboard.score_color)),
+            ));
+        });
+}